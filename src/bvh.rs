@@ -0,0 +1,627 @@
+use crate::intersection::{intersections, Intersection, Intersections};
+use crate::matrix::Matrix;
+use crate::ray::Ray;
+use crate::shape::{same_shape, Motion, Shape, ShapeBase};
+use crate::triangle::TriangleTest;
+use crate::tuple::{Aabb, Point, Vector};
+
+#[cfg(test)]
+mod tests_for_bvh {
+    use crate::bvh::*;
+    use crate::f64_helpers::EPS;
+    use crate::group::group;
+    use crate::plane::plane;
+    use crate::ray::ray;
+    use crate::sphere::sphere;
+    use crate::transform::{scaling, translation};
+    use crate::triangle::triangle;
+    use crate::tuple::{point, vector};
+
+    // a 10x10x10 lattice of small spheres, 3 units apart
+    fn lattice() -> Vec<Box<dyn Shape>> {
+        let mut shapes: Vec<Box<dyn Shape>> = vec![];
+        for i in 0..1000 {
+            let mut s = sphere();
+            let (x, y, z) = ((i % 10) as f64, (i / 10 % 10) as f64, (i / 100) as f64);
+            s.set_transform(translation(x * 3., y * 3., z * 3.) * scaling(0.5, 0.5, 0.5));
+            shapes.push(Box::new(s));
+        }
+        shapes
+    }
+
+    fn rays() -> Vec<Ray> {
+        let mut rays = vec![];
+        for i in 0..50 {
+            let f = i as f64;
+            rays.push(ray(
+                point(-5., f * 0.6, f * 0.55 - 1.),
+                vector(1., 0.1 * (f % 7.) - 0.3, 0.05 * (f % 5.)),
+            ));
+        }
+        rays
+    }
+
+    fn ts(xs: Intersections) -> Vec<f64> {
+        xs.iter().map(|i| i.t).collect()
+    }
+
+    #[test]
+    fn a_few_shapes_fit_in_a_single_leaf() {
+        let b = bvh(vec![Box::new(sphere()), Box::new(sphere())]);
+        assert_eq!(b.node_count(), 1);
+        assert_eq!(b.children().len(), 2);
+    }
+
+    #[test]
+    fn many_shapes_are_split_into_a_tree() {
+        let b = bvh(lattice());
+        assert!(b.node_count() > 100);
+        assert_eq!(b.bounds().min, point(-0.5, -0.5, -0.5));
+        assert_eq!(b.bounds().max, point(27.5, 27.5, 27.5));
+        // every node's box holds everything beneath it
+        for (index, node) in b.nodes.iter().enumerate() {
+            if node.count > 0 {
+                for child in &b.children[node.offset..node.offset + node.count] {
+                    assert!(node.bounds.contains_box(&child.parent_space_bounds()));
+                }
+            } else {
+                assert!(node.bounds.contains_box(&b.nodes[index + 1].bounds));
+                assert!(node.bounds.contains_box(&b.nodes[node.offset].bounds));
+            }
+        }
+    }
+
+    #[test]
+    fn it_finds_the_same_intersections_as_a_group() {
+        let b = bvh(lattice());
+        let mut g = group();
+        for shape in lattice() {
+            g.add_child(shape);
+        }
+        for r in rays() {
+            assert_eq!(ts(b.intersect(&r)), ts(g.intersect(&r)));
+        }
+    }
+
+    #[test]
+    fn its_hits_are_on_its_children() {
+        let b = bvh(lattice());
+        let r = ray(point(-5., 0., 0.), vector(1., 0., 0.));
+        let xs = b.intersect(&r);
+        assert_eq!(xs.len(), 20);
+        assert!(b.includes(xs[0].object));
+        assert!(b
+            .children()
+            .iter()
+            .any(|child| same_shape(child.as_ref(), xs[0].object)));
+    }
+
+    #[test]
+    fn unbounded_shapes_are_kept_out_of_the_tree_but_still_hit() {
+        let mut shapes = lattice();
+        shapes.push(Box::new(plane()));
+        let b = bvh(shapes);
+        assert!(b.bounds().max.x.is_infinite());
+        let r = ray(point(1.5, 5., 1.5), vector(0., -1., 0.));
+        assert_eq!(b.intersect(&r).len(), 1);
+    }
+
+    #[test]
+    fn it_can_be_transformed_as_a_whole() {
+        let mut b = bvh(lattice());
+        b.set_transform(translation(0., 0., 10.));
+        let r = ray(point(-5., 0., 0.), vector(1., 0., 0.));
+        assert!(b.intersect(&r).is_empty());
+        let r = ray(point(-5., 0., 10.), vector(1., 0., 0.));
+        let xs = b.intersect(&r);
+        assert_eq!(xs.len(), 20);
+        let n = xs[0].object.normal_at(r.position(xs[0].t), &xs[0]);
+        assert_eq!(n, vector(-1., 0., 0.));
+    }
+
+    #[test]
+    fn its_nearest_hit_is_the_first_of_its_intersections() {
+        let mut shapes = lattice();
+        shapes.push(Box::new(plane()));
+        let b = bvh(shapes);
+        let mut all = rays();
+        all.push(ray(point(13.5, 13.5, 13.5), vector(-1., -0.2, 0.1)));
+        all.push(ray(point(1.5, 5., 1.5), vector(0., -1., 0.)));
+        all.push(ray(point(1.5, 5., 1.5), vector(0., 1., 0.)));
+        for r in all {
+            let xs = b.intersect(&r);
+            let expected = xs.hit();
+            let hit = b.hit(&r);
+            assert_eq!(hit.map(|i| i.t), expected.map(|i| i.t));
+            if let (Some(hit), Some(expected)) = (hit, expected) {
+                assert!(same_shape(hit.object, expected.object));
+            }
+        }
+    }
+
+    #[test]
+    fn shadow_rays_stop_at_the_first_hit_before_the_light() {
+        let b = bvh(lattice());
+        let r = ray(point(-5., 0., 0.), vector(1., 0., 0.));
+        assert!(b.hits_before(&r, 5.));
+        assert!(!b.hits_before(&r, 4.));
+        let r = ray(point(1.5, 1.5, -5.), vector(0., 0., 1.));
+        assert!(!b.hits_before(&r, 100.));
+    }
+
+    #[test]
+    fn refitting_follows_children_that_have_moved() {
+        let mut b = bvh(lattice());
+        for child in b.children_mut() {
+            if child.parent_space_bounds().centre() == point(0., 0., 0.) {
+                child.set_transform(translation(0., 0., -50.) * scaling(0.5, 0.5, 0.5));
+            }
+        }
+        let r = ray(point(0., 0., -60.), vector(0., 0., 1.));
+        b.refit();
+        assert_eq!(b.bounds().min, point(-0.5, -0.5, -50.5));
+        let xs = b.intersect(&r);
+        assert_abs_diff_eq!(xs[0].t, 9.5, epsilon = EPS);
+        assert_eq!(xs.len(), 20);
+    }
+
+    #[test]
+    fn small_moves_are_refitted_without_a_rebuild() {
+        let mut b = bvh(lattice());
+        let cost = b.cost();
+        for child in b.children_mut() {
+            let moved = translation(0.1, 0., 0.) * *child.transform();
+            child.set_transform(moved);
+        }
+        assert!(!b.update());
+        assert!(b.cost() < cost * b.rebuild_threshold);
+        let r = ray(point(-5., 0., 0.), vector(1., 0., 0.));
+        assert_abs_diff_eq!(b.intersect(&r)[0].t, 4.6, epsilon = EPS);
+    }
+
+    #[test]
+    fn scattering_the_children_triggers_a_rebuild() {
+        let mut b = bvh(lattice());
+        let cost = b.cost();
+        for (i, child) in b.children_mut().iter_mut().enumerate() {
+            // send neighbours in the tree to opposite ends of the lattice
+            let j = (i * 7919) % 1000;
+            let (x, y, z) = ((j % 10) as f64, (j / 10 % 10) as f64, (j / 100) as f64);
+            child.set_transform(translation(x * 3., y * 3., z * 3.) * scaling(0.5, 0.5, 0.5));
+        }
+        b.refit();
+        assert!(b.cost() > cost * b.rebuild_threshold);
+        assert!(b.update());
+        assert!(b.cost() < cost * b.rebuild_threshold);
+        let r = ray(point(-5., 0., 0.), vector(1., 0., 0.));
+        assert_eq!(b.intersect(&r).len(), 20);
+    }
+
+    #[test]
+    fn a_group_of_triangles_can_be_handed_over() {
+        let mut g = group();
+        for x in 0..10 {
+            for y in 0..10 {
+                let corner = |dx: usize, dy: usize| point((x + dx) as f64, (y + dy) as f64, 0.);
+                g.add_child(Box::new(triangle(corner(0, 0), corner(1, 0), corner(0, 1))));
+                g.add_child(Box::new(triangle(corner(1, 0), corner(1, 1), corner(0, 1))));
+            }
+        }
+        let r = ray(point(3.3, 4.2, -5.), vector(0., 0., 1.));
+        let expected: Vec<f64> = g.intersect(&r).iter().map(|i| i.t).collect();
+        let b = bvh(g.into_children());
+        assert_eq!(b.children().len(), 200);
+        assert!(b.node_count() > 1);
+        let ts: Vec<f64> = b.intersect(&r).iter().map(|i| i.t).collect();
+        assert_eq!(ts, expected);
+        assert_eq!(ts, vec![5.]);
+    }
+}
+
+// Leaves hold at most this many shapes unless they cannot be split.
+const MAX_LEAF_SIZE: usize = 4;
+// Centroids are sorted into this many bins along an axis to find a split,
+// which is far cheaper than trying every shape as a split point.
+const BINS: usize = 12;
+// The cost of visiting a node relative to intersecting one shape.
+const TRAVERSAL_COST: f64 = 1.;
+
+/// One node of the flattened tree. Interior nodes have `count == 0`; their
+/// first child follows them in the array and `offset` is the second. Leaves
+/// hold `children[offset..offset + count]`.
+#[derive(Debug, Clone)]
+struct BvhNode {
+    bounds: Aabb,
+    offset: usize,
+    count: usize,
+    axis: usize,
+}
+
+/// A bounding volume hierarchy: like a group, but with its children sorted
+/// into a tree of boxes so a ray only tests the few shapes near its path.
+///
+/// The tree is built with the surface area heuristic, which splits where
+/// the chance of a ray hitting each side, weighted by how many shapes are
+/// there, is lowest. Shapes with infinite bounds, such as planes, cannot be
+/// placed in the tree and are always tested.
+///
+/// When children move, e.g. between frames of an animation, `update` fits
+/// the boxes around their new positions and only rebuilds the whole tree
+/// once its `cost` has grown past `rebuild_threshold` times what it was.
+#[derive(Debug)]
+pub struct Bvh {
+    base: ShapeBase,
+    children: Vec<Box<dyn Shape>>,
+    nodes: Vec<BvhNode>,
+    unbounded: Vec<Box<dyn Shape>>,
+    built_cost: f64,
+    pub rebuild_threshold: f64,
+}
+
+impl Bvh {
+    pub fn children(&self) -> &[Box<dyn Shape>] {
+        &self.children
+    }
+
+    /// The children in the tree, in the order the tree keeps them rather
+    /// than the order they were given. Call `update` after moving any of
+    /// them, or rays will keep testing the boxes where they used to be.
+    pub fn children_mut(&mut self) -> &mut [Box<dyn Shape>] {
+        &mut self.children
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Builds the tree from scratch around wherever the children are now.
+    pub fn rebuild(&mut self) {
+        let boxes: Vec<Aabb> = self
+            .children
+            .iter()
+            .map(|child| child.parent_space_bounds())
+            .collect();
+        let mut order: Vec<usize> = (0..boxes.len()).collect();
+        self.nodes.clear();
+        if !order.is_empty() {
+            build(&mut self.nodes, &boxes, &mut order, 0);
+        }
+        let mut slots: Vec<Option<Box<dyn Shape>>> = self.children.drain(..).map(Some).collect();
+        self.children = order.iter().map(|&i| slots[i].take().unwrap()).collect();
+        self.built_cost = self.cost();
+    }
+
+    /// Fits every box around the children's current positions, keeping
+    /// the shape of the tree. Children always come after their parent in
+    /// the array, so one backwards pass updates them before it.
+    pub fn refit(&mut self) {
+        for index in (0..self.nodes.len()).rev() {
+            let node = &self.nodes[index];
+            let bounds = if node.count > 0 {
+                self.children[node.offset..node.offset + node.count]
+                    .iter()
+                    .fold(Aabb::default(), |all, child| {
+                        all.merge(&child.parent_space_bounds())
+                    })
+            } else {
+                self.nodes[index + 1]
+                    .bounds
+                    .merge(&self.nodes[node.offset].bounds)
+            };
+            self.nodes[index].bounds = bounds;
+        }
+    }
+
+    /// How expensive the tree is to trace by the surface area heuristic:
+    /// the work a ray through the root box can expect to do, in units of
+    /// intersecting one shape. Refitting after children move apart makes
+    /// boxes overlap and grow, which shows up here.
+    pub fn cost(&self) -> f64 {
+        let root_area = self
+            .nodes
+            .first()
+            .map_or(0., |root| root.bounds.surface_area());
+        if root_area <= 0. {
+            return self.children.len() as f64;
+        }
+        self.nodes
+            .iter()
+            .map(|node| {
+                let work = if node.count > 0 {
+                    node.count as f64
+                } else {
+                    TRAVERSAL_COST
+                };
+                work * node.bounds.surface_area() / root_area
+            })
+            .sum()
+    }
+
+    /// Refits the tree after children have moved, rebuilding it instead if
+    /// refitting has left it too slow. Returns whether it was rebuilt.
+    pub fn update(&mut self) -> bool {
+        self.refit();
+        if self.cost() > self.built_cost * self.rebuild_threshold {
+            self.rebuild();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn update_children(&mut self) {
+        let parents = self.base.parents_for_children();
+        for child in self.children.iter_mut().chain(self.unbounded.iter_mut()) {
+            child.set_parents(parents.clone());
+        }
+    }
+
+    /// Calls `visit` with every shape in a leaf whose boxes the ray passes
+    /// through between `t_min` and `t_max`, nearer subtrees first.
+    ///
+    /// `visit` returns the new `t_max`, so lowering it to the nearest hit
+    /// so far skips every box beyond. Lowering it below `t_min` stops the
+    /// walk, and the final `t_max` is returned.
+    fn traverse<'a, F>(&'a self, ray: &Ray, t_min: f64, mut t_max: f64, mut visit: F) -> f64
+    where
+        F: FnMut(&'a dyn Shape) -> f64,
+    {
+        if self.nodes.is_empty() {
+            return t_max;
+        }
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            match node.bounds.entry_exit(ray) {
+                Some((entry, exit)) if entry <= t_max && exit >= t_min => {}
+                _ => continue,
+            }
+            if node.count > 0 {
+                for child in &self.children[node.offset..node.offset + node.count] {
+                    t_max = visit(child.as_ref());
+                    if t_max < t_min {
+                        return t_max;
+                    }
+                }
+            } else if ray.direction[node.axis] < 0. {
+                stack.push(index + 1);
+                stack.push(node.offset);
+            } else {
+                stack.push(node.offset);
+                stack.push(index + 1);
+            }
+        }
+        t_max
+    }
+}
+
+impl Shape for Bvh {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    fn set_transform(&mut self, transform: Matrix) {
+        self.base.set_transform(transform);
+        self.update_children();
+    }
+
+    fn set_end_transform(&mut self, end: Option<Matrix>) {
+        self.base.set_end_transform(end);
+        self.update_children();
+    }
+
+    fn set_parents(&mut self, parents: Vec<Motion>) {
+        self.base.set_parents(parents);
+        self.update_children();
+    }
+
+    fn set_triangle_test(&mut self, test: TriangleTest) {
+        for child in self.children.iter_mut().chain(self.unbounded.iter_mut()) {
+            child.set_triangle_test(test);
+        }
+    }
+
+    fn includes(&self, other: &dyn Shape) -> bool {
+        same_shape(self, other)
+            || self
+                .children
+                .iter()
+                .chain(self.unbounded.iter())
+                .any(|child| child.includes(other))
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        let mut xs: Vec<Intersection> = self
+            .unbounded
+            .iter()
+            .flat_map(|child| child.intersect(local_ray))
+            .collect();
+        // CSG needs every crossing, so nothing can be pruned here
+        self.traverse(local_ray, -f64::INFINITY, f64::INFINITY, |child| {
+            xs.extend(child.intersect(local_ray));
+            f64::INFINITY
+        });
+        intersections(xs)
+    }
+
+    fn local_hit(&self, local_ray: &Ray) -> Option<Intersection<'_>> {
+        let mut nearest = self
+            .unbounded
+            .iter()
+            .fold(None, |nearest, child| nearer(nearest, child.hit(local_ray)));
+        let t_max = nearest.map_or(f64::INFINITY, |hit| hit.t);
+        self.traverse(local_ray, 0., t_max, |child| {
+            nearest = nearer(nearest, child.hit(local_ray));
+            nearest.map_or(f64::INFINITY, |hit| hit.t)
+        });
+        nearest
+    }
+
+    fn local_hits_before(&self, local_ray: &Ray, max_t: f64) -> bool {
+        self.unbounded
+            .iter()
+            .any(|child| child.hits_before(local_ray, max_t))
+            || self.traverse(local_ray, 0., max_t, |child| {
+                if child.hits_before(local_ray, max_t) {
+                    -f64::INFINITY
+                } else {
+                    max_t
+                }
+            }) < 0.
+    }
+
+    fn local_normal_at(&self, _local_point: Point, _hit: &Intersection) -> Vector {
+        panic!("a BVH has no surface of its own: normals come from its children")
+    }
+
+    fn bounds(&self) -> Aabb {
+        let tree = self
+            .nodes
+            .first()
+            .map_or(Aabb::default(), |root| root.bounds);
+        self.unbounded
+            .iter()
+            .fold(tree, |all, child| all.merge(&child.parent_space_bounds()))
+    }
+}
+
+/// Where to split a node: shapes whose centroid falls in a bin below `bin`
+/// along `axis` go to the first child.
+struct Split {
+    axis: usize,
+    bin: usize,
+    min: f64,
+    extent: f64,
+}
+
+impl Split {
+    fn goes_first(&self, centre: Point) -> bool {
+        bin_of(centre[self.axis], self.min, self.extent) < self.bin
+    }
+}
+
+fn bin_of(position: f64, min: f64, extent: f64) -> usize {
+    (((position - min) / extent * BINS as f64) as usize).min(BINS - 1)
+}
+
+/// The cheapest split of `order` by the surface area heuristic, or `None`
+/// if keeping the shapes together in a leaf is cheaper.
+fn best_split(boxes: &[Aabb], order: &[usize], bounds: &Aabb) -> Option<Split> {
+    let mut centres = Aabb::default();
+    for &i in order {
+        centres.add_point(boxes[i].centre());
+    }
+    let area = bounds.surface_area();
+    let mut best: Option<(f64, Split)> = None;
+    for axis in 0..3 {
+        let (min, extent) = (centres.min[axis], centres.max[axis] - centres.min[axis]);
+        if extent <= 0. {
+            continue;
+        }
+        let mut bins = [(Aabb::default(), 0); BINS];
+        for &i in order {
+            let bin = &mut bins[bin_of(boxes[i].centre()[axis], min, extent)];
+            bin.0 = bin.0.merge(&boxes[i]);
+            bin.1 += 1;
+        }
+        for split in 1..BINS {
+            let side = |bins: &[(Aabb, usize)]| {
+                bins.iter()
+                    .fold((Aabb::default(), 0), |(b, n), (bin, count)| {
+                        (b.merge(bin), n + count)
+                    })
+            };
+            let (first, first_count) = side(&bins[..split]);
+            let (second, second_count) = side(&bins[split..]);
+            if first_count == 0 || second_count == 0 {
+                continue;
+            }
+            let cost = TRAVERSAL_COST
+                + (first.surface_area() * first_count as f64
+                    + second.surface_area() * second_count as f64)
+                    / area;
+            if best.as_ref().is_none_or(|(best_cost, _)| cost < *best_cost) {
+                let split = Split {
+                    axis,
+                    bin: split,
+                    min,
+                    extent,
+                };
+                best = Some((cost, split));
+            }
+        }
+    }
+    match best {
+        Some((cost, _)) if order.len() <= MAX_LEAF_SIZE && cost >= order.len() as f64 => None,
+        Some((_, split)) => Some(split),
+        None => None,
+    }
+}
+
+/// Builds the subtree over `order`, the indices of the shapes that will end
+/// up at `children[offset..]`, reordering it so each leaf is contiguous.
+fn build(nodes: &mut Vec<BvhNode>, boxes: &[Aabb], order: &mut [usize], offset: usize) {
+    let bounds = order
+        .iter()
+        .fold(Aabb::default(), |all, &i| all.merge(&boxes[i]));
+    let index = nodes.len();
+    nodes.push(BvhNode {
+        bounds,
+        offset,
+        count: order.len(),
+        axis: 0,
+    });
+    let split = match best_split(boxes, order, &bounds) {
+        Some(split) => split,
+        None => return,
+    };
+    let mut middle = 0;
+    for i in 0..order.len() {
+        if split.goes_first(boxes[order[i]].centre()) {
+            order.swap(i, middle);
+            middle += 1;
+        }
+    }
+    let (first, second) = order.split_at_mut(middle);
+    build(nodes, boxes, first, offset);
+    let second_index = nodes.len();
+    build(nodes, boxes, second, offset + middle);
+    nodes[index] = BvhNode {
+        bounds,
+        offset: second_index,
+        count: 0,
+        axis: split.axis,
+    };
+}
+
+pub fn bvh(shapes: Vec<Box<dyn Shape>>) -> Bvh {
+    let (children, unbounded) = shapes
+        .into_iter()
+        .partition(|shape| shape.parent_space_bounds().is_finite());
+    let mut b = Bvh {
+        base: ShapeBase::default(),
+        children,
+        nodes: vec![],
+        unbounded,
+        built_cost: 0.,
+        rebuild_threshold: 1.5,
+    };
+    b.rebuild();
+    b.update_children();
+    b
+}
+
+fn nearer<'a>(
+    a: Option<Intersection<'a>>,
+    b: Option<Intersection<'a>>,
+) -> Option<Intersection<'a>> {
+    match (a, b) {
+        (Some(a), Some(b)) if b.t < a.t => Some(b),
+        (None, b) => b,
+        (a, _) => a,
+    }
+}
@@ -0,0 +1,228 @@
+use crate::canvas::{canvas, Canvas};
+use crate::color::BLACK;
+use crate::matrix::Matrix;
+use crate::ray::{ray_at_time, Ray};
+use crate::tuple::point;
+use crate::world::World;
+
+#[cfg(test)]
+mod tests_for_camera {
+    use crate::camera::*;
+    use crate::color::{color, WHITE};
+    use crate::f64_helpers::EPS;
+    use crate::light::point_light;
+    use crate::material::{material, Material};
+    use crate::matrix::IDENTITY;
+    use crate::shape::Shape;
+    use crate::sphere::sphere;
+    use crate::transform::{rotation_y, translation, view_transform};
+    use crate::tuple::vector;
+    use crate::world::{default_world, world};
+    use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+    #[test]
+    fn it_is_built_from_its_size_and_field_of_view() {
+        let c = camera(160, 120, PI / 2.);
+        assert_eq!(c.hsize(), 160);
+        assert_eq!(c.vsize(), 120);
+        assert_abs_diff_eq!(c.field_of_view(), PI / 2.);
+        assert_eq!(*c.transform(), IDENTITY);
+        assert_eq!(c.shutter_samples(), 1);
+    }
+
+    #[test]
+    fn the_pixel_size_for_a_horizontal_canvas() {
+        let c = camera(200, 125, PI / 2.);
+        assert_abs_diff_eq!(c.pixel_size(), 0.01, epsilon = EPS);
+    }
+
+    #[test]
+    fn the_pixel_size_for_a_vertical_canvas() {
+        let c = camera(125, 200, PI / 2.);
+        assert_abs_diff_eq!(c.pixel_size(), 0.01, epsilon = EPS);
+    }
+
+    #[test]
+    fn a_ray_through_the_centre_of_the_canvas() {
+        let c = camera(201, 101, PI / 2.);
+        let r = c.ray_for_pixel(100, 50);
+        assert_eq!(r.origin, point(0., 0., 0.));
+        assert_eq!(r.direction, vector(0., 0., -1.));
+    }
+
+    #[test]
+    fn a_ray_through_a_corner_of_the_canvas() {
+        let c = camera(201, 101, PI / 2.);
+        let r = c.ray_for_pixel(0, 0);
+        assert_eq!(r.origin, point(0., 0., 0.));
+        assert_eq!(r.direction, vector(0.66519, 0.33259, -0.66851));
+    }
+
+    #[test]
+    fn a_ray_when_the_camera_is_transformed() {
+        let mut c = camera(201, 101, PI / 2.);
+        c.set_transform(rotation_y(PI / 4.) * translation(0., -2., 5.));
+        let r = c.ray_for_pixel(100, 50);
+        assert_eq!(r.origin, point(0., 2., -5.));
+        assert_eq!(r.direction, vector(FRAC_1_SQRT_2, 0., -FRAC_1_SQRT_2));
+    }
+
+    #[test]
+    fn rays_can_be_fired_while_the_shutter_is_open() {
+        let c = camera(201, 101, PI / 2.);
+        assert_abs_diff_eq!(c.ray_for_pixel(100, 50).time, 0.);
+        assert_abs_diff_eq!(c.ray_for_pixel_at_time(100, 50, 0.3).time, 0.3);
+    }
+
+    #[test]
+    fn rendering_a_world_with_a_camera() {
+        let w = default_world();
+        let mut c = camera(11, 11, PI / 2.);
+        let from = point(0., 0., -5.);
+        let to = point(0., 0., 0.);
+        let up = vector(0., 1., 0.);
+        c.set_transform(view_transform(from, to, up));
+        let image = c.render(&w);
+        assert_eq!(image.pixel_at(5, 5), color(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    fn moving_objects_are_blurred_across_the_shutter_samples() {
+        let mut w = world();
+        let mut s = sphere();
+        s.set_material(Material {
+            ambient: 1.,
+            diffuse: 0.,
+            specular: 0.,
+            ..material()
+        });
+        s.set_end_transform(Some(translation(10., 0., 0.)));
+        w.objects.push(Box::new(s));
+        w.lights.push(point_light(point(0., 0., -10.), WHITE));
+        let mut c = camera(11, 11, PI / 2.);
+        c.set_transform(view_transform(
+            point(0., 0., -5.),
+            point(0., 0., 0.),
+            vector(0., 1., 0.),
+        ));
+        assert_eq!(c.render(&w).pixel_at(5, 5), WHITE);
+        // the sphere is in front of the camera at time 0 but not at time 0.5
+        c.set_shutter_samples(2);
+        assert_eq!(c.render(&w).pixel_at(5, 5), color(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    #[should_panic]
+    fn it_needs_at_least_one_shutter_sample() {
+        camera(10, 10, PI / 2.).set_shutter_samples(0);
+    }
+}
+
+/// A pinhole camera one unit in front of a canvas of `hsize` by `vsize`
+/// pixels, seeing `field_of_view` radians across the wider side.
+///
+/// The camera sits at the origin looking down -z until given a transform,
+/// usually from `view_transform`. To blur moving objects each pixel can be
+/// sampled at several evenly spaced times while the shutter is open.
+#[derive(Debug, Clone)]
+pub struct Camera {
+    hsize: usize,
+    vsize: usize,
+    field_of_view: f64,
+    transform: Matrix,
+    inverse: Matrix,
+    half_width: f64,
+    half_height: f64,
+    pixel_size: f64,
+    shutter_samples: usize,
+}
+
+impl Camera {
+    pub fn hsize(&self) -> usize {
+        self.hsize
+    }
+
+    pub fn vsize(&self) -> usize {
+        self.vsize
+    }
+
+    pub fn field_of_view(&self) -> f64 {
+        self.field_of_view
+    }
+
+    pub fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Matrix) {
+        self.transform = transform;
+        self.inverse = transform.inverse();
+    }
+
+    /// The width of a pixel, in world units one unit in front of the camera.
+    pub fn pixel_size(&self) -> f64 {
+        self.pixel_size
+    }
+
+    pub fn shutter_samples(&self) -> usize {
+        self.shutter_samples
+    }
+
+    pub fn set_shutter_samples(&mut self, samples: usize) {
+        if samples == 0 {
+            panic!("a camera needs at least one shutter sample");
+        }
+        self.shutter_samples = samples;
+    }
+
+    /// The ray from the camera through the centre of a pixel, at the moment
+    /// the shutter opens.
+    pub fn ray_for_pixel(&self, px: usize, py: usize) -> Ray {
+        self.ray_for_pixel_at_time(px, py, 0.)
+    }
+
+    pub fn ray_for_pixel_at_time(&self, px: usize, py: usize, time: f64) -> Ray {
+        // the canvas is at z = -1, with +x to the left as the camera looks
+        let world_x = self.half_width - (px as f64 + 0.5) * self.pixel_size;
+        let world_y = self.half_height - (py as f64 + 0.5) * self.pixel_size;
+        let pixel = self.inverse * point(world_x, world_y, -1.);
+        let origin = self.inverse * point(0., 0., 0.);
+        ray_at_time(origin, (pixel - origin).normalize(), time)
+    }
+
+    pub fn render(&self, world: &World) -> Canvas {
+        let mut image = canvas(self.hsize, self.vsize);
+        let samples = self.shutter_samples as f64;
+        for y in 0..self.vsize {
+            for x in 0..self.hsize {
+                let total = (0..self.shutter_samples).fold(BLACK, |total, i| {
+                    let time = i as f64 / samples;
+                    total + world.color_at(&self.ray_for_pixel_at_time(x, y, time))
+                });
+                image.write_pixel(x, y, total * (1. / samples));
+            }
+        }
+        image
+    }
+}
+
+pub fn camera(hsize: usize, vsize: usize, field_of_view: f64) -> Camera {
+    let half_view = (field_of_view / 2.).tan();
+    let aspect = hsize as f64 / vsize as f64;
+    let (half_width, half_height) = if aspect >= 1. {
+        (half_view, half_view / aspect)
+    } else {
+        (half_view * aspect, half_view)
+    };
+    Camera {
+        hsize,
+        vsize,
+        field_of_view,
+        transform: Matrix::identity(),
+        inverse: Matrix::identity(),
+        half_width,
+        half_height,
+        pixel_size: half_width * 2. / hsize as f64,
+        shutter_samples: 1,
+    }
+}
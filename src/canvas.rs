@@ -4,7 +4,7 @@ use std::ops::{Index, IndexMut};
 #[cfg(test)]
 mod tests_for_canvas {
     use crate::canvas::*;
-    use crate::color::{color, RED};
+    use crate::color::{color, RED, WHITE};
 
     #[test]
     fn it_can_be_instatiated_with_a_width_and_height() {
@@ -83,27 +83,92 @@ mod tests_for_canvas {
                         0 0 0 0 0 0 0 0 0 0 0 0 0 0 255";
         assert_eq!(expected, ppm_body);
     }
-}
 
-const MAX_COLOR: u8 = 255;
-const NUM_COLOR_VALS: u16 = MAX_COLOR as u16 + 1;
+    #[test]
+    fn it_writes_pixels_a_row_at_a_time() {
+        let mut canv = canvas(2, 3);
+        canv.write_pixel(1, 0, RED);
+        let expected = "0 0 0 255 0 0\n\
+                        0 0 0 0 0 0\n\
+                        0 0 0 0 0 0";
+        assert_eq!(expected, canv._ppm_body());
+    }
 
-#[derive(Debug, Clone)]
-struct PpmRowBytes(Vec<u8>);
+    #[test]
+    fn a_wide_canvas_is_written_row_by_row_not_column_by_column() {
+        let mut canv = canvas(3, 2);
+        canv.write_pixel(2, 0, RED);
+        canv.write_pixel(0, 1, WHITE);
+        let expected = "0 0 0 0 0 0 255 0 0\n\
+                        255 255 255 0 0 0 0 0 0";
+        assert_eq!(expected, canv._ppm_body());
+    }
 
-impl Index<usize> for PpmRowBytes {
-    type Output = u8;
-    fn index(&self, index: usize) -> &u8 {
-        &self.0[index]
+    #[test]
+    fn it_can_be_read_from_a_ppm_file() {
+        let ppm = "P3\n\
+                   # a comment\n\
+                   2 2\n\
+                   255\n\
+                   255 0 0  0 255 0\n\
+                   0 0 255  255 255 255\n";
+        let c = canvas_from_ppm(ppm).unwrap();
+        assert_eq!(c.width(), 2);
+        assert_eq!(c.height(), 2);
+        assert_eq!(c[0][0], RED);
+        assert_eq!(c[1][0], color(0., 1., 0.));
+        assert_eq!(c[0][1], color(0., 0., 1.));
+        assert_eq!(c[1][1], color(1., 1., 1.));
+    }
+
+    #[test]
+    fn it_scales_read_colors_by_the_maximum_color_value() {
+        let c = canvas_from_ppm("P3 1 1 100 50 0 100").unwrap();
+        assert_eq!(c[0][0], color(0.5, 0., 1.));
+    }
+
+    #[test]
+    fn it_round_trips_through_a_ppm_file() {
+        let mut canv = canvas(3, 2);
+        canv.write_pixel(2, 0, RED);
+        canv.write_pixel(0, 1, WHITE);
+        let read = canvas_from_ppm(&canv.to_ppm()).unwrap();
+        assert_eq!(read[2][0], RED);
+        assert_eq!(read[0][1], WHITE);
+        assert_eq!(read[1][1], BLACK);
+    }
+
+    #[test]
+    fn it_rejects_files_that_are_not_plain_ppm() {
+        assert!(canvas_from_ppm("P6 1 1 255").is_err());
+        assert!(canvas_from_ppm("P3 2 2 255 0 0 0").is_err());
+        assert!(canvas_from_ppm("P3 1 1 255 0 zero 0").is_err());
+    }
+
+    #[test]
+    fn it_rejects_sizes_that_are_not_whole_numbers() {
+        assert!(canvas_from_ppm("P3 2.5 1 255 0 0 0 0 0 0").is_err());
+        assert!(canvas_from_ppm("P3 -3 1 255 0 0 0").is_err());
+        assert!(canvas_from_ppm("P3 1e12 1 255 0 0 0").is_err());
+    }
+
+    #[test]
+    fn it_rejects_an_enormous_size_without_the_pixels_to_fill_it() {
+        assert!(canvas_from_ppm("P3 1000000000000 1000000000000 255 0 0 0").is_err());
+        assert!(canvas_from_ppm("P3 100000 100000 255 0 0 0").is_err());
     }
-}
 
-impl IndexMut<usize> for PpmRowBytes {
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        &mut self.0[index]
+    #[test]
+    fn it_rejects_a_maximum_color_value_out_of_range() {
+        assert!(canvas_from_ppm("P3 1 1 0 0 0 0").is_err());
+        assert!(canvas_from_ppm("P3 1 1 65536 0 0 0").is_err());
+        assert!(canvas_from_ppm("P3 1 1 65535 0 0 0").is_ok());
     }
 }
 
+const MAX_COLOR: u8 = 255;
+const NUM_COLOR_VALS: u16 = MAX_COLOR as u16 + 1;
+
 #[derive(Debug, Clone)]
 pub struct Canvas {
     width: usize,
@@ -132,8 +197,10 @@ impl Canvas {
     fn _ppm_pixels(&self) -> Vec<u8> {
         let mut bytes = vec![0u8; self._buffer_size()];
         let mut i_rgb = 0;
-        for row in &self.pixels {
-            for pixel in row {
+        // PPM is written a row at a time but we store pixels by column
+        for row_index in 0..self.height {
+            for column in &self.pixels {
+                let pixel = column[row_index];
                 bytes[i_rgb] = clamp_to_byte(pixel.red);
                 bytes[i_rgb + 1] = clamp_to_byte(pixel.green);
                 bytes[i_rgb + 2] = clamp_to_byte(pixel.blue);
@@ -170,6 +237,14 @@ impl Canvas {
         }
     }
 
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
     pub fn pixel_at(self, column_index: usize, row_index: usize) -> Color {
         self.pixels[column_index][row_index]
     }
@@ -208,6 +283,72 @@ pub fn write_pixel(canvas: &mut Canvas, column_index: usize, row_index: usize, c
     canvas.write_pixel(column_index, row_index, color)
 }
 
+/// Reads a plain (`P3`) PPM image, as written by `Canvas::to_ppm`.
+///
+/// Comments are skipped and colors are scaled by the file's maximum color
+/// value so they come back in the range 0 to 1.
+pub fn canvas_from_ppm(ppm: &str) -> Result<Canvas, String> {
+    let mut tokens = ppm
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .flat_map(|line| line.split_whitespace());
+    match tokens.next() {
+        Some("P3") => {}
+        other => return Err(format!("expected PPM magic number P3, found {:?}", other)),
+    }
+    let mut next_size = |what: &str| -> Result<usize, String> {
+        let token = tokens
+            .next()
+            .ok_or_else(|| format!("PPM ended before {}", what))?;
+        token
+            .parse::<usize>()
+            .map_err(|_| format!("expected a whole number for {}, found {:?}", what, token))
+    };
+    let width = next_size("the width")?;
+    let height = next_size("the height")?;
+    let max_color = next_size("the maximum color value")?;
+    if max_color == 0 || max_color > 65535 {
+        return Err(format!(
+            "maximum color value must be from 1 to 65535, not {}",
+            max_color
+        ));
+    }
+    // check the pixels are all there before making room for them, so a
+    // bogus size in the header cannot ask for an enormous canvas
+    let values: Vec<&str> = tokens.collect();
+    let needed = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .ok_or_else(|| format!("a {}x{} image is too large", width, height))?;
+    if values.len() < needed {
+        return Err(format!(
+            "expected {} color values for a {}x{} image, found {}",
+            needed,
+            width,
+            height,
+            values.len()
+        ));
+    }
+    let value = |i: usize| -> Result<f64, String> {
+        values[i]
+            .parse::<f64>()
+            .map(|v| v / max_color as f64)
+            .map_err(|_| format!("expected a number for a color value, found {:?}", values[i]))
+    };
+    let mut c = canvas(width, height);
+    for row_index in 0..height {
+        for column_index in 0..width {
+            let i = (row_index * width + column_index) * 3;
+            c.pixels[column_index][row_index] = Color {
+                red: value(i)?,
+                green: value(i + 1)?,
+                blue: value(i + 2)?,
+            };
+        }
+    }
+    Ok(c)
+}
+
 fn clamp_to_byte(color_component: f64) -> u8 {
     match color_component {
         x if x <= 0.0 => 0,
@@ -132,11 +132,7 @@ impl Mul<Color> for f64 {
 }
 
 pub fn color(red: f64, green: f64, blue: f64) -> Color {
-    Color {
-        red: red,
-        green: green,
-        blue: blue,
-    }
+    Color { red, green, blue }
 }
 
 pub const BLACK: Color = Color {
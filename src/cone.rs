@@ -0,0 +1,211 @@
+use crate::cylinder::within_radius;
+use crate::f64_helpers::EPS;
+use crate::intersection::{intersection, intersections, Intersection, Intersections};
+use crate::ray::Ray;
+use crate::shape::{Shape, ShapeBase};
+use crate::tuple::{aabb, point, vector, Aabb, Point, Vector};
+
+#[cfg(test)]
+mod tests_for_cone {
+    use crate::cone::*;
+    use crate::ray::ray;
+    use crate::tuple::point;
+
+    #[test]
+    fn a_ray_strikes_it() {
+        let shape = cone();
+        let cases = [
+            (point(0., 0., -5.), vector(0., 0., 1.), 5., 5.),
+            (point(0., 0., -5.), vector(1., 1., 1.), 8.66025, 8.66025),
+            (point(1., 1., -5.), vector(-0.5, -1., 1.), 4.55006, 49.44994),
+        ];
+        for (origin, direction, t0, t1) in cases.iter() {
+            let r = ray(*origin, direction.normalize());
+            let xs = shape.local_intersect(&r);
+            assert_eq!(xs.len(), 2);
+            assert_abs_diff_eq!(xs[0].t, t0, epsilon = 0.0001);
+            assert_abs_diff_eq!(xs[1].t, t1, epsilon = 0.0001);
+        }
+    }
+
+    #[test]
+    fn a_ray_parallel_to_one_half_hits_the_other_once() {
+        let shape = cone();
+        let r = ray(point(0., 0., -1.), vector(0., 1., 1.).normalize());
+        let xs = shape.local_intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_abs_diff_eq!(xs[0].t, 0.35355, epsilon = EPS);
+    }
+
+    #[test]
+    fn a_closed_cone_is_hit_on_its_caps() {
+        let shape = Cone {
+            minimum: -0.5,
+            maximum: 0.5,
+            closed: true,
+            ..cone()
+        };
+        let cases = [
+            (point(0., 0., -5.), vector(0., 1., 0.), 0),
+            (point(0., 0., -0.25), vector(0., 1., 1.), 2),
+            (point(0., 0., -0.25), vector(0., 1., 0.), 4),
+        ];
+        for (origin, direction, count) in cases.iter() {
+            let r = ray(*origin, direction.normalize());
+            assert_eq!(shape.local_intersect(&r).len(), *count);
+        }
+    }
+
+    #[test]
+    fn a_ray_along_the_axis_of_a_closed_cone_hits_both_caps_and_the_apex() {
+        let shape = Cone {
+            minimum: -1.,
+            maximum: 2.,
+            closed: true,
+            ..cone()
+        };
+        let xs = shape.local_intersect(&ray(point(0., 5., 0.), vector(0., -1., 0.)));
+        let ts: Vec<f64> = xs.iter().map(|i| i.t).collect();
+        assert_eq!(ts.first(), Some(&3.));
+        assert_eq!(ts.last(), Some(&6.));
+    }
+
+    #[test]
+    fn its_normal_points_away_from_the_surface() {
+        let shape = cone();
+        let root2 = f64::sqrt(2.);
+        let cases = [
+            (point(0., 0., 0.), vector(0., 0., 0.)),
+            (point(1., 1., 1.), vector(1., -root2, 1.)),
+            (point(-1., -1., 0.), vector(-1., 1., 0.)),
+        ];
+        for (p, expected) in cases.iter() {
+            assert_eq!(
+                shape.local_normal_at(*p, &intersection(0., &shape)),
+                *expected
+            );
+        }
+    }
+
+    #[test]
+    fn its_normal_on_the_end_caps() {
+        let shape = Cone {
+            minimum: -1.,
+            maximum: 2.,
+            closed: true,
+            ..cone()
+        };
+        assert_eq!(
+            shape.local_normal_at(point(0.5, 2., 0.), &intersection(0., &shape)),
+            vector(0., 1., 0.)
+        );
+        assert_eq!(
+            shape.local_normal_at(point(0.5, -1., 0.), &intersection(0., &shape)),
+            vector(0., -1., 0.)
+        );
+    }
+}
+
+/// A double-napped cone around the y axis with its apex at the origin:
+/// the radius at any height y is |y|.
+///
+/// Like `Cylinder` it is infinite unless truncated by `minimum` and
+/// `maximum`, and only capped when `closed` is set.
+#[derive(Debug, Clone)]
+pub struct Cone {
+    base: ShapeBase,
+    pub minimum: f64,
+    pub maximum: f64,
+    pub closed: bool,
+}
+
+impl Cone {
+    fn intersect_caps<'a>(&'a self, local_ray: &Ray, xs: &mut Vec<Intersection<'a>>) {
+        if !self.closed || local_ray.direction.y.abs() < EPS {
+            return;
+        }
+        for &cap_y in [self.minimum, self.maximum].iter() {
+            let t = (cap_y - local_ray.origin.y) / local_ray.direction.y;
+            if within_radius(local_ray, t, cap_y.abs()) {
+                xs.push(intersection(t, self));
+            }
+        }
+    }
+
+    fn push_if_in_bounds<'a>(&'a self, local_ray: &Ray, t: f64, xs: &mut Vec<Intersection<'a>>) {
+        let y = local_ray.origin.y + t * local_ray.direction.y;
+        if self.minimum < y && y < self.maximum {
+            xs.push(intersection(t, self));
+        }
+    }
+}
+
+impl Default for Cone {
+    fn default() -> Cone {
+        Cone {
+            base: ShapeBase::default(),
+            minimum: -f64::INFINITY,
+            maximum: f64::INFINITY,
+            closed: false,
+        }
+    }
+}
+
+impl Shape for Cone {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        let mut xs = vec![];
+        let d = local_ray.direction;
+        let o = local_ray.origin;
+        let a = d.x * d.x - d.y * d.y + d.z * d.z;
+        let b = 2. * o.x * d.x - 2. * o.y * d.y + 2. * o.z * d.z;
+        let c = o.x * o.x - o.y * o.y + o.z * o.z;
+        if a.abs() < EPS {
+            // parallel to one nappe: at most a single hit on the other
+            if b.abs() >= EPS {
+                self.push_if_in_bounds(local_ray, -c / (2. * b), &mut xs);
+            }
+        } else {
+            let discriminant = b * b - 4. * a * c;
+            if discriminant >= -EPS {
+                let root = discriminant.max(0.).sqrt();
+                self.push_if_in_bounds(local_ray, (-b - root) / (2. * a), &mut xs);
+                self.push_if_in_bounds(local_ray, (-b + root) / (2. * a), &mut xs);
+            }
+        }
+        self.intersect_caps(local_ray, &mut xs);
+        intersections(xs)
+    }
+
+    fn local_normal_at(&self, local_point: Point, _hit: &Intersection) -> Vector {
+        let distance = local_point.x * local_point.x + local_point.z * local_point.z;
+        if distance < self.maximum * self.maximum && local_point.y >= self.maximum - EPS {
+            vector(0., 1., 0.)
+        } else if distance < self.minimum * self.minimum && local_point.y <= self.minimum + EPS {
+            vector(0., -1., 0.)
+        } else {
+            let y = distance.sqrt();
+            let y = if local_point.y > 0. { -y } else { y };
+            vector(local_point.x, y, local_point.z)
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        let radius = self.minimum.abs().max(self.maximum.abs());
+        aabb(
+            point(-radius, self.minimum, -radius),
+            point(radius, self.maximum, radius),
+        )
+    }
+}
+
+pub fn cone() -> Cone {
+    Cone::default()
+}
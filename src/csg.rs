@@ -0,0 +1,260 @@
+use crate::intersection::{intersections, Intersection, Intersections};
+use crate::matrix::Matrix;
+use crate::ray::Ray;
+use crate::shape::{Motion, Shape, ShapeBase};
+use crate::triangle::TriangleTest;
+use crate::tuple::{Aabb, Point, Vector};
+
+#[cfg(test)]
+mod tests_for_csg {
+    use crate::csg::*;
+    use crate::cube::cube;
+    use crate::intersection::intersection;
+    use crate::ray::ray;
+    use crate::shape::same_shape;
+    use crate::sphere::sphere;
+    use crate::transform::{scaling, translation};
+    use crate::tuple::{point, vector};
+
+    #[test]
+    fn it_is_created_with_an_operation_and_two_shapes() {
+        let c = csg(CsgOperation::Union, Box::new(sphere()), Box::new(cube()));
+        assert_eq!(c.operation, CsgOperation::Union);
+        assert!(c.includes(c.left()));
+        assert!(c.includes(c.right()));
+        assert!(!c.includes(&sphere()));
+    }
+
+    #[test]
+    fn the_rules_for_each_operation() {
+        use CsgOperation::*;
+        let cases = [
+            (Union, true, true, true, false),
+            (Union, true, true, false, true),
+            (Union, true, false, true, false),
+            (Union, true, false, false, true),
+            (Union, false, true, true, false),
+            (Union, false, true, false, false),
+            (Union, false, false, true, true),
+            (Union, false, false, false, true),
+            (Intersection, true, true, true, true),
+            (Intersection, true, true, false, false),
+            (Intersection, true, false, true, true),
+            (Intersection, true, false, false, false),
+            (Intersection, false, true, true, true),
+            (Intersection, false, true, false, true),
+            (Intersection, false, false, true, false),
+            (Intersection, false, false, false, false),
+            (Difference, true, true, true, false),
+            (Difference, true, true, false, true),
+            (Difference, true, false, true, false),
+            (Difference, true, false, false, true),
+            (Difference, false, true, true, true),
+            (Difference, false, true, false, true),
+            (Difference, false, false, true, false),
+            (Difference, false, false, false, false),
+        ];
+        for &(op, lhit, inl, inr, expected) in cases.iter() {
+            assert_eq!(intersection_allowed(op, lhit, inl, inr), expected);
+        }
+    }
+
+    #[test]
+    fn filtering_keeps_the_right_intersections_for_each_operation() {
+        let cases = [
+            (CsgOperation::Union, 0, 3),
+            (CsgOperation::Intersection, 1, 2),
+            (CsgOperation::Difference, 0, 1),
+        ];
+        for &(op, x0, x1) in cases.iter() {
+            let c = csg(op, Box::new(sphere()), Box::new(cube()));
+            let (s1, s2) = (c.left(), c.right());
+            let xs = intersections(vec![
+                intersection(1., s1),
+                intersection(2., s2),
+                intersection(3., s1),
+                intersection(4., s2),
+            ]);
+            let result = c.filter_intersections(xs.clone());
+            assert_eq!(result.len(), 2);
+            assert_eq!(result[0], xs[x0]);
+            assert_eq!(result[1], xs[x1]);
+        }
+    }
+
+    #[test]
+    fn a_ray_misses_it() {
+        let c = csg(CsgOperation::Union, Box::new(sphere()), Box::new(cube()));
+        let r = ray(point(0., 2., -5.), vector(0., 0., 1.));
+        assert!(c.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn a_ray_hits_it() {
+        let s1 = sphere();
+        let mut s2 = sphere();
+        s2.set_transform(translation(0., 0., 0.5));
+        let c = csg(CsgOperation::Union, Box::new(s1), Box::new(s2));
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let xs = c.local_intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_abs_diff_eq!(xs[0].t, 4.);
+        assert!(same_shape(xs[0].object, c.left()));
+        assert_abs_diff_eq!(xs[1].t, 6.5);
+        assert!(same_shape(xs[1].object, c.right()));
+    }
+
+    #[test]
+    fn a_hole_drilled_through_a_cube_lets_rays_pass() {
+        let mut drill = sphere();
+        drill.set_transform(scaling(0.5, 5., 0.5));
+        let c = csg(CsgOperation::Difference, Box::new(cube()), Box::new(drill));
+        let through_hole = ray(point(0., 5., 0.), vector(0., -1., 0.));
+        assert!(c.intersect(&through_hole).is_empty());
+        let beside_hole = ray(point(0.8, 5., 0.), vector(0., -1., 0.));
+        assert_eq!(c.intersect(&beside_hole).len(), 2);
+    }
+
+    #[test]
+    fn its_children_pick_up_its_transform() {
+        let mut c = csg(CsgOperation::Union, Box::new(sphere()), Box::new(cube()));
+        c.set_transform(translation(5., 0., 0.));
+        let n = c
+            .left()
+            .normal_at(point(6., 0., 0.), &intersection(0., c.left()));
+        assert_eq!(n, vector(1., 0., 0.));
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+/// Constructive solid geometry: two shapes combined by an operation, e.g.
+/// a cube with a sphere subtracted from it.
+#[derive(Debug)]
+pub struct Csg {
+    base: ShapeBase,
+    pub operation: CsgOperation,
+    left: Box<dyn Shape>,
+    right: Box<dyn Shape>,
+}
+
+impl Csg {
+    pub fn left(&self) -> &dyn Shape {
+        self.left.as_ref()
+    }
+
+    pub fn right(&self) -> &dyn Shape {
+        self.right.as_ref()
+    }
+
+    /// Keep only the intersections that lie on the surface of the combined
+    /// shape, tracking whether we are inside each child as we go.
+    pub fn filter_intersections<'a>(&self, xs: Intersections<'a>) -> Intersections<'a> {
+        let mut in_left = false;
+        let mut in_right = false;
+        let mut result = vec![];
+        for i in xs {
+            let left_hit = self.left.includes(i.object);
+            if intersection_allowed(self.operation, left_hit, in_left, in_right) {
+                result.push(i);
+            }
+            if left_hit {
+                in_left = !in_left;
+            } else {
+                in_right = !in_right;
+            }
+        }
+        intersections(result)
+    }
+
+    fn update_children(&mut self) {
+        let parents = self.base.parents_for_children();
+        self.left.set_parents(parents.clone());
+        self.right.set_parents(parents);
+    }
+}
+
+impl Shape for Csg {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    fn set_transform(&mut self, transform: Matrix) {
+        self.base.set_transform(transform);
+        self.update_children();
+    }
+
+    fn set_end_transform(&mut self, end: Option<Matrix>) {
+        self.base.set_end_transform(end);
+        self.update_children();
+    }
+
+    fn set_parents(&mut self, parents: Vec<Motion>) {
+        self.base.set_parents(parents);
+        self.update_children();
+    }
+
+    fn set_triangle_test(&mut self, test: TriangleTest) {
+        self.left.set_triangle_test(test);
+        self.right.set_triangle_test(test);
+    }
+
+    fn includes(&self, other: &dyn Shape) -> bool {
+        self.left.includes(other) || self.right.includes(other)
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        let xs = self
+            .left
+            .intersect(local_ray)
+            .into_iter()
+            .chain(self.right.intersect(local_ray))
+            .collect();
+        self.filter_intersections(intersections(xs))
+    }
+
+    fn local_normal_at(&self, _local_point: Point, _hit: &Intersection) -> Vector {
+        panic!("a CSG shape has no surface of its own: normals come from its children")
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.left
+            .parent_space_bounds()
+            .merge(&self.right.parent_space_bounds())
+    }
+}
+
+/// Whether a hit on the left (`left_hit`) or right child survives `op`,
+/// given whether the ray is currently inside the left and right children.
+pub fn intersection_allowed(
+    op: CsgOperation,
+    left_hit: bool,
+    in_left: bool,
+    in_right: bool,
+) -> bool {
+    match op {
+        CsgOperation::Union => (left_hit && !in_right) || (!left_hit && !in_left),
+        CsgOperation::Intersection => (left_hit && in_right) || (!left_hit && in_left),
+        CsgOperation::Difference => (left_hit && !in_right) || (!left_hit && in_left),
+    }
+}
+
+pub fn csg(operation: CsgOperation, left: Box<dyn Shape>, right: Box<dyn Shape>) -> Csg {
+    let mut c = Csg {
+        base: ShapeBase::default(),
+        operation,
+        left,
+        right,
+    };
+    c.update_children();
+    c
+}
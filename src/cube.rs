@@ -0,0 +1,139 @@
+use crate::intersection::{intersection, intersections, Intersection, Intersections};
+use crate::ray::Ray;
+use crate::shape::{Shape, ShapeBase};
+use crate::tuple::{aabb, check_slab, point, vector, Aabb, Point, Vector};
+
+#[cfg(test)]
+mod tests_for_cube {
+    use crate::cube::*;
+    use crate::ray::ray;
+    use crate::tuple::point;
+
+    #[test]
+    fn a_ray_intersects_each_face() {
+        let c = cube();
+        let cases = [
+            (point(5., 0.5, 0.), vector(-1., 0., 0.), 4., 6.),
+            (point(-5., 0.5, 0.), vector(1., 0., 0.), 4., 6.),
+            (point(0.5, 5., 0.), vector(0., -1., 0.), 4., 6.),
+            (point(0.5, -5., 0.), vector(0., 1., 0.), 4., 6.),
+            (point(0.5, 0., 5.), vector(0., 0., -1.), 4., 6.),
+            (point(0.5, 0., -5.), vector(0., 0., 1.), 4., 6.),
+            (point(0., 0.5, 0.), vector(0., 0., 1.), -1., 1.),
+        ];
+        for (origin, direction, t1, t2) in cases.iter() {
+            let xs = c.local_intersect(&ray(*origin, *direction));
+            assert_eq!(xs.len(), 2);
+            assert_abs_diff_eq!(xs[0].t, t1);
+            assert_abs_diff_eq!(xs[1].t, t2);
+        }
+    }
+
+    #[test]
+    fn a_ray_misses() {
+        let c = cube();
+        let cases = [
+            (point(-2., 0., 0.), vector(0.2673, 0.5345, 0.8018)),
+            (point(0., -2., 0.), vector(0.8018, 0.2673, 0.5345)),
+            (point(0., 0., -2.), vector(0.5345, 0.8018, 0.2673)),
+            (point(2., 0., 2.), vector(0., 0., -1.)),
+            (point(0., 2., 2.), vector(0., -1., 0.)),
+            (point(2., 2., 0.), vector(-1., 0., 0.)),
+        ];
+        for (origin, direction) in cases.iter() {
+            assert!(c.local_intersect(&ray(*origin, *direction)).is_empty());
+        }
+    }
+
+    #[test]
+    fn a_ray_parallel_to_a_face_inside_the_slab_hits() {
+        let c = cube();
+        let xs = c.local_intersect(&ray(point(-5., 0.99, 0.5), vector(1., 0., 0.)));
+        assert_eq!(xs.len(), 2);
+        assert_abs_diff_eq!(xs[0].t, 4.);
+        assert_abs_diff_eq!(xs[1].t, 6.);
+    }
+
+    #[test]
+    fn a_ray_grazing_an_edge_hits_at_a_single_point() {
+        let c = cube();
+        let xs = c.local_intersect(&ray(point(-2., 0., 0.), vector(1., 1., 0.)));
+        assert_eq!(xs.len(), 2);
+        assert_abs_diff_eq!(xs[0].t, 1.);
+        assert_abs_diff_eq!(xs[1].t, 1.);
+    }
+
+    #[test]
+    fn its_normal_is_taken_from_the_face() {
+        let c = cube();
+        let cases = [
+            (point(1., 0.5, -0.8), vector(1., 0., 0.)),
+            (point(-1., -0.2, 0.9), vector(-1., 0., 0.)),
+            (point(-0.4, 1., -0.1), vector(0., 1., 0.)),
+            (point(0.3, -1., -0.7), vector(0., -1., 0.)),
+            (point(-0.6, 0.3, 1.), vector(0., 0., 1.)),
+            (point(0.4, 0.4, -1.), vector(0., 0., -1.)),
+            (point(1., 1., 1.), vector(1., 0., 0.)),
+            (point(-1., -1., -1.), vector(-1., 0., 0.)),
+        ];
+        for (p, expected) in cases.iter() {
+            assert_eq!(c.local_normal_at(*p, &intersection(0., &c)), *expected);
+        }
+    }
+}
+
+/// An axis-aligned cube spanning -1 to 1 on every axis.
+#[derive(Debug, Clone, Default)]
+pub struct Cube {
+    base: ShapeBase,
+}
+
+impl Shape for Cube {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    // Slab method: the ray is inside the cube where it is between
+    // all three pairs of parallel planes at once.
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        let (xtmin, xtmax) = check_axis(local_ray.origin.x, local_ray.direction.x);
+        let (ytmin, ytmax) = check_axis(local_ray.origin.y, local_ray.direction.y);
+        let (ztmin, ztmax) = check_axis(local_ray.origin.z, local_ray.direction.z);
+        let tmin = xtmin.max(ytmin).max(ztmin);
+        let tmax = xtmax.min(ytmax).min(ztmax);
+        if tmin > tmax {
+            return intersections(vec![]);
+        }
+        intersections(vec![intersection(tmin, self), intersection(tmax, self)])
+    }
+
+    fn local_normal_at(&self, local_point: Point, _hit: &Intersection) -> Vector {
+        let x = local_point.x.abs();
+        let y = local_point.y.abs();
+        let z = local_point.z.abs();
+        if x >= y && x >= z {
+            vector(local_point.x, 0., 0.)
+        } else if y >= z {
+            vector(0., local_point.y, 0.)
+        } else {
+            vector(0., 0., local_point.z)
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        aabb(point(-1., -1., -1.), point(1., 1., 1.))
+    }
+}
+
+/// Where a ray enters and leaves the slab between -1 and 1 on one axis.
+pub fn check_axis(origin: f64, direction: f64) -> (f64, f64) {
+    check_slab(origin, direction, -1., 1.)
+}
+
+pub fn cube() -> Cube {
+    Cube::default()
+}
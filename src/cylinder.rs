@@ -0,0 +1,241 @@
+use crate::f64_helpers::EPS;
+use crate::intersection::{intersection, intersections, Intersection, Intersections};
+use crate::ray::Ray;
+use crate::shape::{Shape, ShapeBase};
+use crate::tuple::{aabb, point, vector, Aabb, Point, Vector};
+
+#[cfg(test)]
+mod tests_for_cylinder {
+    use crate::cylinder::*;
+    use crate::ray::ray;
+    use crate::tuple::point;
+
+    #[test]
+    fn a_ray_misses() {
+        let cyl = cylinder();
+        let cases = [
+            (point(1., 0., 0.), vector(0., 1., 0.)),
+            (point(0., 0., 0.), vector(0., 1., 0.)),
+            (point(0., 0., -5.), vector(1., 1., 1.)),
+        ];
+        for (origin, direction) in cases.iter() {
+            let r = ray(*origin, direction.normalize());
+            assert!(cyl.local_intersect(&r).is_empty());
+        }
+    }
+
+    #[test]
+    fn a_ray_strikes_it() {
+        let cyl = cylinder();
+        let cases = [
+            (point(1., 0., -5.), vector(0., 0., 1.), 5., 5.),
+            (point(0., 0., -5.), vector(0., 0., 1.), 4., 6.),
+            (point(0.5, 0., -5.), vector(0.1, 1., 1.), 6.80798, 7.08872),
+        ];
+        for (origin, direction, t0, t1) in cases.iter() {
+            let r = ray(*origin, direction.normalize());
+            let xs = cyl.local_intersect(&r);
+            assert_eq!(xs.len(), 2);
+            assert_abs_diff_eq!(xs[0].t, t0, epsilon = EPS);
+            assert_abs_diff_eq!(xs[1].t, t1, epsilon = EPS);
+        }
+    }
+
+    #[test]
+    fn its_normal_points_away_from_the_axis() {
+        let cyl = cylinder();
+        let cases = [
+            (point(1., 0., 0.), vector(1., 0., 0.)),
+            (point(0., 5., -1.), vector(0., 0., -1.)),
+            (point(0., -2., 1.), vector(0., 0., 1.)),
+            (point(-1., 1., 0.), vector(-1., 0., 0.)),
+        ];
+        for (p, expected) in cases.iter() {
+            assert_eq!(cyl.local_normal_at(*p, &intersection(0., &cyl)), *expected);
+        }
+    }
+
+    #[test]
+    fn it_is_infinite_and_open_by_default() {
+        let cyl = cylinder();
+        assert_eq!(cyl.minimum, -f64::INFINITY);
+        assert_eq!(cyl.maximum, f64::INFINITY);
+        assert!(!cyl.closed);
+    }
+
+    #[test]
+    fn a_truncated_cylinder_is_only_hit_between_its_bounds() {
+        let cyl = Cylinder {
+            minimum: 1.,
+            maximum: 2.,
+            ..cylinder()
+        };
+        let cases = [
+            (point(0., 1.5, 0.), vector(0.1, 1., 0.), 0),
+            (point(0., 3., -5.), vector(0., 0., 1.), 0),
+            (point(0., 0., -5.), vector(0., 0., 1.), 0),
+            (point(0., 2., -5.), vector(0., 0., 1.), 0),
+            (point(0., 1., -5.), vector(0., 0., 1.), 0),
+            (point(0., 1.5, -2.), vector(0., 0., 1.), 2),
+        ];
+        for (origin, direction, count) in cases.iter() {
+            let r = ray(*origin, direction.normalize());
+            assert_eq!(cyl.local_intersect(&r).len(), *count);
+        }
+    }
+
+    #[test]
+    fn a_closed_cylinder_is_hit_on_its_caps() {
+        let cyl = Cylinder {
+            minimum: 1.,
+            maximum: 2.,
+            closed: true,
+            ..cylinder()
+        };
+        let cases = [
+            (point(0., 3., 0.), vector(0., -1., 0.), 2),
+            (point(0., 3., -2.), vector(0., -1., 2.), 2),
+            (point(0., 4., -2.), vector(0., -1., 1.), 2),
+            (point(0., 0., -2.), vector(0., 1., 2.), 2),
+            (point(0., -1., -2.), vector(0., 1., 1.), 2),
+        ];
+        for (origin, direction, count) in cases.iter() {
+            let r = ray(*origin, direction.normalize());
+            assert_eq!(cyl.local_intersect(&r).len(), *count);
+        }
+    }
+
+    #[test]
+    fn a_ray_parallel_to_the_axis_hits_both_caps() {
+        let cyl = Cylinder {
+            minimum: 1.,
+            maximum: 2.,
+            closed: true,
+            ..cylinder()
+        };
+        let xs = cyl.local_intersect(&ray(point(0.5, 5., 0.), vector(0., -1., 0.)));
+        assert_eq!(xs.len(), 2);
+        assert_abs_diff_eq!(xs[0].t, 3.);
+        assert_abs_diff_eq!(xs[1].t, 4.);
+    }
+
+    #[test]
+    fn its_normal_on_the_end_caps() {
+        let cyl = Cylinder {
+            minimum: 1.,
+            maximum: 2.,
+            closed: true,
+            ..cylinder()
+        };
+        let cases = [
+            (point(0., 1., 0.), vector(0., -1., 0.)),
+            (point(0.5, 1., 0.), vector(0., -1., 0.)),
+            (point(0., 1., 0.5), vector(0., -1., 0.)),
+            (point(0., 2., 0.), vector(0., 1., 0.)),
+            (point(0.5, 2., 0.), vector(0., 1., 0.)),
+            (point(0., 2., 0.5), vector(0., 1., 0.)),
+        ];
+        for (p, expected) in cases.iter() {
+            assert_eq!(cyl.local_normal_at(*p, &intersection(0., &cyl)), *expected);
+        }
+    }
+}
+
+/// A cylinder of radius 1 around the y axis.
+///
+/// It is infinitely long unless `minimum` and `maximum` truncate it, and
+/// truncated ends are only capped when `closed` is set.
+#[derive(Debug, Clone)]
+pub struct Cylinder {
+    base: ShapeBase,
+    pub minimum: f64,
+    pub maximum: f64,
+    pub closed: bool,
+}
+
+impl Cylinder {
+    fn intersect_caps<'a>(&'a self, local_ray: &Ray, xs: &mut Vec<Intersection<'a>>) {
+        if !self.closed || local_ray.direction.y.abs() < EPS {
+            return;
+        }
+        for &cap_y in [self.minimum, self.maximum].iter() {
+            let t = (cap_y - local_ray.origin.y) / local_ray.direction.y;
+            if within_radius(local_ray, t, 1.) {
+                xs.push(intersection(t, self));
+            }
+        }
+    }
+}
+
+impl Default for Cylinder {
+    fn default() -> Cylinder {
+        Cylinder {
+            base: ShapeBase::default(),
+            minimum: -f64::INFINITY,
+            maximum: f64::INFINITY,
+            closed: false,
+        }
+    }
+}
+
+impl Shape for Cylinder {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        let mut xs = vec![];
+        let d = local_ray.direction;
+        let o = local_ray.origin;
+        let a = d.x * d.x + d.z * d.z;
+        // a ray parallel to the y axis can only ever hit the caps
+        if a.abs() >= EPS {
+            let b = 2. * o.x * d.x + 2. * o.z * d.z;
+            let c = o.x * o.x + o.z * o.z - 1.;
+            let discriminant = b * b - 4. * a * c;
+            if discriminant < 0. {
+                return intersections(xs);
+            }
+            let root = discriminant.sqrt();
+            for &t in [(-b - root) / (2. * a), (-b + root) / (2. * a)].iter() {
+                let y = o.y + t * d.y;
+                if self.minimum < y && y < self.maximum {
+                    xs.push(intersection(t, self));
+                }
+            }
+        }
+        self.intersect_caps(local_ray, &mut xs);
+        intersections(xs)
+    }
+
+    fn local_normal_at(&self, local_point: Point, _hit: &Intersection) -> Vector {
+        let distance = local_point.x * local_point.x + local_point.z * local_point.z;
+        if distance < 1. && local_point.y >= self.maximum - EPS {
+            vector(0., 1., 0.)
+        } else if distance < 1. && local_point.y <= self.minimum + EPS {
+            vector(0., -1., 0.)
+        } else {
+            vector(local_point.x, 0., local_point.z)
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        aabb(point(-1., self.minimum, -1.), point(1., self.maximum, 1.))
+    }
+}
+
+/// Whether the ray at `t` lies within `radius` of the y axis; used to
+/// decide whether a ray hits a cap.
+pub fn within_radius(local_ray: &Ray, t: f64, radius: f64) -> bool {
+    let x = local_ray.origin.x + t * local_ray.direction.x;
+    let z = local_ray.origin.z + t * local_ray.direction.z;
+    x * x + z * z <= radius * radius + EPS
+}
+
+pub fn cylinder() -> Cylinder {
+    Cylinder::default()
+}
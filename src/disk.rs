@@ -0,0 +1,125 @@
+use crate::f64_helpers::EPS;
+use crate::intersection::{intersection, intersections, Intersection, Intersections};
+use crate::ray::Ray;
+use crate::shape::{Shape, ShapeBase};
+use crate::tuple::{aabb, point, vector, Aabb, Point, Vector};
+
+#[cfg(test)]
+mod tests_for_disk {
+    use crate::disk::*;
+    use crate::ray::ray;
+    use crate::shape::same_shape;
+    use crate::tuple::point;
+
+    #[test]
+    fn it_is_a_solid_disk_by_default() {
+        assert_abs_diff_eq!(disk().inner_radius, 0.);
+    }
+
+    #[test]
+    fn a_ray_through_the_middle_hits() {
+        let d = disk();
+        let r = ray(point(0., 1., 0.), vector(0., -1., 0.));
+        let xs = d.local_intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_abs_diff_eq!(xs[0].t, 1.);
+        assert!(same_shape(xs[0].object, &d));
+    }
+
+    #[test]
+    fn a_ray_outside_the_radius_misses() {
+        let d = disk();
+        let r = ray(point(1.5, 1., 0.), vector(0., -1., 0.));
+        assert!(d.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn a_ray_parallel_to_it_misses() {
+        let d = disk();
+        let r = ray(point(-2., 0., 0.), vector(1., 0., 0.));
+        assert!(d.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn a_ray_grazing_the_rim_hits() {
+        let d = disk();
+        let r = ray(point(0., 1., 0.), vector(1., -1., 0.));
+        let xs = d.local_intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_abs_diff_eq!(xs[0].t, 1.);
+    }
+
+    #[test]
+    fn a_ray_through_the_hole_of_an_annulus_misses() {
+        let a = annulus(0.5);
+        let through_hole = ray(point(0.2, 1., 0.), vector(0., -1., 0.));
+        assert!(a.local_intersect(&through_hole).is_empty());
+        let through_ring = ray(point(0., 1., 0.75), vector(0., -1., 0.));
+        assert_eq!(a.local_intersect(&through_ring).len(), 1);
+    }
+
+    #[test]
+    fn its_normal_points_up() {
+        let d = disk();
+        assert_eq!(
+            d.local_normal_at(point(0.5, 0., 0.), &intersection(0., &d)),
+            vector(0., 1., 0.)
+        );
+        assert_eq!(
+            d.local_normal_at(point(0., 0., -1.), &intersection(0., &d)),
+            vector(0., 1., 0.)
+        );
+    }
+}
+
+/// A flat disk of radius 1 in the xz plane, centred on the origin.
+///
+/// A non-zero `inner_radius` cuts a hole out of the middle, making an annulus.
+#[derive(Debug, Clone, Default)]
+pub struct Disk {
+    base: ShapeBase,
+    pub inner_radius: f64,
+}
+
+impl Shape for Disk {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        if local_ray.direction.y.abs() < EPS {
+            return intersections(vec![]);
+        }
+        let t = -local_ray.origin.y / local_ray.direction.y;
+        let p = local_ray.position(t);
+        let distance_squared = p.x * p.x + p.z * p.z;
+        let inner_squared = self.inner_radius * self.inner_radius;
+        if distance_squared > 1. + EPS || distance_squared < inner_squared {
+            return intersections(vec![]);
+        }
+        intersections(vec![intersection(t, self)])
+    }
+
+    fn local_normal_at(&self, _local_point: Point, _hit: &Intersection) -> Vector {
+        vector(0., 1., 0.)
+    }
+
+    fn bounds(&self) -> Aabb {
+        aabb(point(-1., 0., -1.), point(1., 0., 1.))
+    }
+}
+
+pub fn disk() -> Disk {
+    Disk::default()
+}
+
+pub fn annulus(inner_radius: f64) -> Disk {
+    Disk {
+        inner_radius,
+        ..Disk::default()
+    }
+}
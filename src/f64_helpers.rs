@@ -1,3 +1,255 @@
+// Our convention is to put each module's tests above the code they exercise.
+#![allow(clippy::items_after_test_module)]
+
 use std::f64;
+use std::f64::consts::PI;
+
+#[cfg(test)]
+mod tests_for_f64_helpers {
+    use crate::f64_helpers::*;
+
+    fn assert_roots(got: Vec<f64>, expected: &[f64]) {
+        assert_eq!(got.len(), expected.len(), "roots {:?}", got);
+        for (g, e) in got.iter().zip(expected.iter()) {
+            assert_abs_diff_eq!(g, e, epsilon = EPS);
+        }
+    }
+
+    #[test]
+    fn quadratic_with_two_roots() {
+        assert_roots(solve_quadratic(1., -3., 2.), &[1., 2.]);
+        assert_roots(solve_quadratic(2., 0., -8.), &[-2., 2.]);
+    }
+
+    #[test]
+    fn quadratic_with_a_repeated_root() {
+        assert_roots(solve_quadratic(1., -2., 1.), &[1., 1.]);
+    }
+
+    #[test]
+    fn quadratic_with_no_real_roots() {
+        assert_roots(solve_quadratic(1., 0., 1.), &[]);
+    }
+
+    #[test]
+    fn quadratic_degenerating_to_linear() {
+        assert_roots(solve_quadratic(0., 2., -4.), &[2.]);
+        assert_roots(solve_quadratic(0., 0., 1.), &[]);
+    }
+
+    #[test]
+    fn quadratic_does_not_cancel_catastrophically() {
+        // the naive formula loses the small root entirely here
+        let roots = solve_quadratic(1., -1e8, 1.);
+        assert_abs_diff_eq!(roots[0], 1e-8, epsilon = 1e-20);
+        assert_abs_diff_eq!(roots[1], 1e8, epsilon = EPS);
+    }
+
+    #[test]
+    fn cubic_with_three_roots() {
+        // (x - 1)(x - 2)(x - 3)
+        assert_roots(solve_cubic(1., -6., 11., -6.), &[1., 2., 3.]);
+    }
+
+    #[test]
+    fn cubic_with_one_root() {
+        // (x - 2)(x^2 + 1)
+        assert_roots(solve_cubic(1., -2., 1., -2.), &[2.]);
+    }
+
+    #[test]
+    fn cubic_with_a_repeated_root() {
+        // (x - 1)^2 (x + 2)
+        assert_roots(solve_cubic(1., 0., -3., 2.), &[-2., 1., 1.]);
+    }
+
+    #[test]
+    fn cubic_degenerating_to_quadratic() {
+        assert_roots(solve_cubic(0., 1., -3., 2.), &[1., 2.]);
+    }
+
+    #[test]
+    fn quartic_with_four_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(solve_quartic(1., -10., 35., -50., 24.), &[1., 2., 3., 4.]);
+    }
+
+    #[test]
+    fn quartic_with_two_roots() {
+        // (x - 1)(x + 1)(x^2 + 1)
+        assert_roots(solve_quartic(1., 0., 0., 0., -1.), &[-1., 1.]);
+    }
+
+    #[test]
+    fn quartic_with_no_real_roots() {
+        assert_roots(solve_quartic(1., 0., 2., 0., 1.), &[]);
+    }
+
+    #[test]
+    fn quartic_that_is_biquadratic() {
+        // (x^2 - 1)(x^2 - 4)
+        assert_roots(solve_quartic(1., 0., -5., 0., 4.), &[-2., -1., 1., 2.]);
+    }
+
+    #[test]
+    fn quartic_with_widely_spread_roots() {
+        // (x - 0.001)(x - 1)(x - 10)(x - 1000)
+        let (r1, r2, r3, r4) = (0.001, 1., 10., 1000.);
+        let b = -(r1 + r2 + r3 + r4);
+        let c = r1 * r2 + r1 * r3 + r1 * r4 + r2 * r3 + r2 * r4 + r3 * r4;
+        let d = -(r1 * r2 * r3 + r1 * r2 * r4 + r1 * r3 * r4 + r2 * r3 * r4);
+        let e = r1 * r2 * r3 * r4;
+        assert_roots(solve_quartic(1., b, c, d, e), &[r1, r2, r3, r4]);
+    }
+}
+
+// Tolerance for comparing computed floats: `f64::EPSILON` is far too tight
+// once values have been through a few matrix multiplications.
+pub const EPS: f64 = 0.00001;
+
+// Roots found analytically are refined with a few Newton steps against the
+// original polynomial, which mops up most of the error the closed forms
+// accumulate when roots are close together or widely spread.
+const NEWTON_STEPS: usize = 4;
+
+/// Real roots of `a x^2 + b x + c`, sorted ascending.
+///
+/// Uses the form that avoids subtracting nearly equal numbers, so both
+/// roots stay accurate even when one is tiny compared to the other.
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a == 0. {
+        return if b == 0. { vec![] } else { vec![-c / b] };
+    }
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        return vec![];
+    }
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let mut roots = if q == 0. {
+        // b and c are both zero
+        vec![0., 0.]
+    } else {
+        vec![q / a, c / q]
+    };
+    sort(&mut roots);
+    roots
+}
+
+/// Real roots of `a x^3 + b x^2 + c x + d`, sorted ascending.
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if a == 0. {
+        return solve_quadratic(b, c, d);
+    }
+    let coefficients = [a, b, c, d];
+    let (b, c, d) = (b / a, c / a, d / a);
+    // substitute x = y - b/3 to get the depressed cubic y^3 + p y + q
+    let shift = b / 3.;
+    let p = c - b * shift;
+    let q = 2. * shift * shift * shift - shift * c + d;
+    let half_q = q / 2.;
+    let third_p = p / 3.;
+    let discriminant = half_q * half_q + third_p * third_p * third_p;
+    let mut roots = if discriminant > 0. {
+        // one real root: Cardano
+        let root = discriminant.sqrt();
+        vec![(-half_q + root).cbrt() + (-half_q - root).cbrt() - shift]
+    } else if third_p == 0. {
+        vec![-shift]
+    } else {
+        // three real roots: trigonometric form avoids complex arithmetic
+        let m = 2. * (-third_p).sqrt();
+        let cos_arg = (3. * q / (p * m)).clamp(-1., 1.);
+        let theta = cos_arg.acos() / 3.;
+        (0..3)
+            .map(|k| m * (theta - 2. * PI * k as f64 / 3.).cos() - shift)
+            .collect()
+    };
+    polish(&mut roots, &coefficients);
+    sort(&mut roots);
+    roots
+}
+
+/// Real roots of `a x^4 + b x^3 + c x^2 + d x + e`, sorted ascending.
+///
+/// Ferrari's method via a resolvent cubic, followed by Newton polishing.
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    if a == 0. {
+        return solve_cubic(b, c, d, e);
+    }
+    let coefficients = [a, b, c, d, e];
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+    // substitute x = y - b/4 to get the depressed quartic y^4 + p y^2 + q y + r
+    let shift = b / 4.;
+    let b2 = b * b;
+    let p = c - 3. * b2 / 8.;
+    let q = d - b * c / 2. + b2 * b / 8.;
+    let r = e - b * d / 4. + b2 * c / 16. - 3. * b2 * b2 / 256.;
+
+    let mut roots = if q.abs() < 1e-12 {
+        // biquadratic: a quadratic in y^2
+        solve_quadratic(1., p, r)
+            .into_iter()
+            .filter(|&z| z >= -1e-12)
+            .flat_map(|z| {
+                let y = z.max(0.).sqrt();
+                vec![y, -y]
+            })
+            .collect()
+    } else {
+        // any real root m > 0 of the resolvent cubic factors the quartic
+        // into two quadratics in y
+        let resolvent = solve_cubic(8., 8. * p, 2. * p * p - 8. * r, -q * q);
+        let m = resolvent.into_iter().fold(f64::NAN, f64::max);
+        if m.is_nan() || m <= 0. {
+            vec![]
+        } else {
+            let root_2m = (2. * m).sqrt();
+            let mut ys = solve_quadratic(1., root_2m, p / 2. + m - q / (2. * root_2m));
+            ys.extend(solve_quadratic(
+                1.,
+                -root_2m,
+                p / 2. + m + q / (2. * root_2m),
+            ));
+            ys
+        }
+    };
+    for root in roots.iter_mut() {
+        *root -= shift;
+    }
+    polish(&mut roots, &coefficients);
+    sort(&mut roots);
+    roots
+}
+
+/// Evaluates the polynomial with `coefficients` ordered highest power first.
+fn evaluate(coefficients: &[f64], x: f64) -> (f64, f64) {
+    let mut value = 0.;
+    let mut derivative = 0.;
+    for &coefficient in coefficients {
+        derivative = derivative * x + value;
+        value = value * x + coefficient;
+    }
+    (value, derivative)
+}
+
+fn polish(roots: &mut [f64], coefficients: &[f64]) {
+    for root in roots.iter_mut() {
+        for _ in 0..NEWTON_STEPS {
+            let (value, derivative) = evaluate(coefficients, *root);
+            if derivative == 0. {
+                break;
+            }
+            let next = *root - value / derivative;
+            // stop rather than wander off when Newton stops improving,
+            // which happens near repeated roots
+            if evaluate(coefficients, next).0.abs() >= value.abs() {
+                break;
+            }
+            *root = next;
+        }
+    }
+}
 
-pub const EPS: f64 = f64::EPSILON;
+fn sort(roots: &mut [f64]) {
+    roots.sort_by(|a, b| a.total_cmp(b));
+}
@@ -0,0 +1,353 @@
+use crate::intersection::{intersections, Intersection, Intersections};
+use crate::matrix::Matrix;
+use crate::ray::Ray;
+use crate::shape::{same_shape, Motion, Shape, ShapeBase};
+use crate::triangle::TriangleTest;
+use crate::tuple::{Aabb, Point, Vector};
+use std::sync::OnceLock;
+
+#[cfg(test)]
+mod tests_for_group {
+    use crate::cylinder::cylinder;
+    use crate::group::*;
+    use crate::intersection::intersection;
+    use crate::matrix::IDENTITY;
+    use crate::ray::{ray, ray_at_time};
+    use crate::shape::same_shape;
+    use crate::sphere::sphere;
+    use crate::transform::{rotation_y, scaling, translation};
+    use crate::tuple::{point, vector};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn it_starts_empty_with_the_identity_transform() {
+        let g = group();
+        assert_eq!(*g.transform(), IDENTITY);
+        assert!(g.children().is_empty());
+    }
+
+    #[test]
+    fn a_child_can_be_added() {
+        let mut g = group();
+        g.add_child(Box::new(sphere()));
+        assert_eq!(g.children().len(), 1);
+    }
+
+    #[test]
+    fn intersecting_an_empty_group_misses() {
+        let g = group();
+        let r = ray(point(0., 0., 0.), vector(0., 0., 1.));
+        assert!(g.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn intersecting_a_nonempty_group_hits_its_children_in_order() {
+        let mut g = group();
+        let s1 = sphere();
+        let mut s2 = sphere();
+        s2.set_transform(translation(0., 0., -3.));
+        let mut s3 = sphere();
+        s3.set_transform(translation(5., 0., 0.));
+        g.add_child(Box::new(s1));
+        g.add_child(Box::new(s2));
+        g.add_child(Box::new(s3));
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let xs = g.local_intersect(&r);
+        assert_eq!(xs.len(), 4);
+        let (s1, s2) = (g.children()[0].as_ref(), g.children()[1].as_ref());
+        assert!(same_shape(xs[0].object, s2));
+        assert!(same_shape(xs[1].object, s2));
+        assert!(same_shape(xs[2].object, s1));
+        assert!(same_shape(xs[3].object, s1));
+    }
+
+    #[test]
+    fn intersecting_a_transformed_group_transforms_its_children() {
+        let mut g = group();
+        g.set_transform(scaling(2., 2., 2.));
+        let mut s = sphere();
+        s.set_transform(translation(5., 0., 0.));
+        g.add_child(Box::new(s));
+        let r = ray(point(10., 0., -10.), vector(0., 0., 1.));
+        assert_eq!(g.intersect(&r).len(), 2);
+    }
+
+    fn nested_sphere() -> Group {
+        let mut g1 = group();
+        g1.set_transform(rotation_y(PI / 2.));
+        let mut g2 = group();
+        g2.set_transform(scaling(1., 2., 3.));
+        let mut s = sphere();
+        s.set_transform(translation(5., 0., 0.));
+        g2.add_child(Box::new(s));
+        g1.add_child(Box::new(g2));
+        g1
+    }
+
+    // The nested sphere sits on the -z axis in world space, so the first
+    // thing a ray down that axis hits is the innermost child.
+    fn innermost(g1: &Group) -> &dyn Shape {
+        let r = ray(point(0., 0., 0.), vector(0., 0., -1.));
+        g1.intersect(&r)[0].object
+    }
+
+    #[test]
+    fn a_point_is_converted_from_world_to_object_space_through_every_parent() {
+        let mut g1 = group();
+        g1.set_transform(rotation_y(PI / 2.));
+        let mut g2 = group();
+        g2.set_transform(scaling(2., 2., 2.));
+        let mut s = sphere();
+        s.set_transform(translation(5., 0., 0.));
+        g2.add_child(Box::new(s));
+        g1.add_child(Box::new(g2));
+        let s = innermost(&g1);
+        assert_eq!(
+            s.world_to_object(point(-2., 0., -10.), 0.),
+            point(0., 0., -1.)
+        );
+    }
+
+    #[test]
+    fn a_normal_is_converted_from_object_to_world_space_through_every_parent() {
+        let g1 = nested_sphere();
+        let s = innermost(&g1);
+        let third = f64::sqrt(3.) / 3.;
+        let n = s.normal_to_world(vector(third, third, third), 0.);
+        assert_eq!(n, vector(0.28571, 0.42857, -0.85714));
+    }
+
+    #[test]
+    fn the_normal_on_a_child_object_accounts_for_every_parent() {
+        let g1 = nested_sphere();
+        let s = innermost(&g1);
+        let n = s.normal_at(point(1.73205, 1.1547, -5.5774), &intersection(0., s));
+        assert_eq!(n, vector(0.2857, 0.42854, -0.85716));
+    }
+
+    #[test]
+    fn transforming_a_group_after_adding_children_still_moves_them() {
+        let mut g2 = group();
+        let mut s = sphere();
+        s.set_transform(translation(5., 0., 0.));
+        g2.add_child(Box::new(s));
+        let mut g1 = group();
+        g1.add_child(Box::new(g2));
+        g1.set_transform(rotation_y(PI / 2.));
+        g1.children_mut()[0].set_transform(scaling(2., 2., 2.));
+        let s = innermost(&g1);
+        assert_eq!(
+            s.world_to_object(point(-2., 0., -10.), 0.),
+            point(0., 0., -1.)
+        );
+    }
+
+    #[test]
+    fn a_moving_group_carries_its_children_with_it() {
+        let mut g = group();
+        g.add_child(Box::new(sphere()));
+        g.set_end_transform(Some(translation(10., 0., 0.) * rotation_y(PI / 2.)));
+        let r = ray_at_time(point(10., 0., -5.), vector(0., 0., 1.), 1.);
+        let xs = g.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        let n = xs[0].object.normal_at(r.position(xs[0].t), &xs[0]);
+        assert_eq!(n, vector(0., 0., -1.));
+        let r = ray_at_time(point(10., 0., -5.), vector(0., 0., 1.), 0.);
+        assert!(g.intersect(&r).is_empty());
+        let max_x = g.parent_space_bounds().max.x;
+        assert!(max_x >= 11.);
+        assert_abs_diff_eq!(max_x, 11., epsilon = 0.01);
+    }
+
+    #[test]
+    fn it_includes_its_descendants() {
+        let g1 = nested_sphere();
+        let s = innermost(&g1);
+        assert!(g1.includes(s));
+        assert!(g1.includes(g1.children()[0].as_ref()));
+        assert!(!g1.includes(&sphere()));
+    }
+
+    #[test]
+    fn its_bounds_contain_all_its_children() {
+        let mut s = sphere();
+        s.set_transform(translation(2., 5., -3.) * scaling(2., 2., 2.));
+        let mut g = group();
+        g.add_child(Box::new(s));
+        let mut c = cylinder();
+        c.minimum = -2.;
+        c.maximum = 2.;
+        c.set_transform(translation(-4., -1., 4.) * scaling(0.5, 1., 0.5));
+        g.add_child(Box::new(c));
+        assert_eq!(g.bounds().min, point(-4.5, -3., -5.));
+        assert_eq!(g.bounds().max, point(4., 7., 4.5));
+    }
+
+    #[test]
+    fn its_bounds_follow_children_moved_after_adding() {
+        let mut g = group();
+        g.add_child(Box::new(sphere()));
+        assert_eq!(g.bounds().max, point(1., 1., 1.));
+        g.children_mut()[0].set_transform(translation(5., 0., 0.));
+        assert_eq!(g.bounds().max, point(6., 1., 1.));
+    }
+
+    #[test]
+    fn it_is_hit_before_a_distance_if_any_child_is() {
+        let mut g = group();
+        let mut s = sphere();
+        s.set_transform(translation(0., 0., 5.));
+        g.add_child(Box::new(s));
+        g.set_transform(scaling(2., 2., 2.));
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        assert!(g.hits_before(&r, 15.));
+        assert!(!g.hits_before(&r, 12.));
+    }
+
+    #[test]
+    fn a_ray_missing_a_childs_bounds_does_not_test_it() {
+        let mut g = group();
+        g.add_child(Box::new(sphere()));
+        let mut far = sphere();
+        far.set_transform(translation(10., 0., 0.));
+        g.add_child(Box::new(far));
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let xs = g.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert!(same_shape(xs[0].object, g.children()[0].as_ref()));
+        let r = ray(point(0., 5., -5.), vector(0., 0., 1.));
+        assert!(g.intersect(&r).is_empty());
+    }
+}
+
+/// A collection of shapes that are transformed together as one unit.
+///
+/// Children are positioned relative to the group, and the group's own
+/// transform moves all of them at once.
+///
+/// The bounds of the group and of each child are worked out the first time
+/// they are needed and kept until the children change, so a ray can skip
+/// every child whose box it misses.
+#[derive(Debug, Default)]
+pub struct Group {
+    base: ShapeBase,
+    children: Vec<Box<dyn Shape>>,
+    bounds: OnceLock<(Aabb, Vec<Aabb>)>,
+}
+
+impl Group {
+    pub fn children(&self) -> &[Box<dyn Shape>] {
+        &self.children
+    }
+
+    pub fn children_mut(&mut self) -> &mut [Box<dyn Shape>] {
+        // the caller may move children around, so bounds must be rebuilt
+        self.bounds = OnceLock::new();
+        &mut self.children
+    }
+
+    pub fn add_child(&mut self, mut child: Box<dyn Shape>) {
+        child.set_parents(self.base.parents_for_children());
+        self.children.push(child);
+        self.bounds = OnceLock::new();
+    }
+
+    fn cached_bounds(&self) -> &(Aabb, Vec<Aabb>) {
+        self.bounds.get_or_init(|| {
+            let child_bounds: Vec<Aabb> = self
+                .children
+                .iter()
+                .map(|child| child.parent_space_bounds())
+                .collect();
+            let bounds = child_bounds
+                .iter()
+                .fold(Aabb::default(), |all, b| all.merge(b));
+            (bounds, child_bounds)
+        })
+    }
+
+    /// Hands back the children, e.g. to put a mesh into a `Bvh`.
+    pub fn into_children(self) -> Vec<Box<dyn Shape>> {
+        self.children
+    }
+
+    fn update_children(&mut self) {
+        let parents = self.base.parents_for_children();
+        for child in self.children.iter_mut() {
+            child.set_parents(parents.clone());
+        }
+    }
+}
+
+impl Shape for Group {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    fn set_transform(&mut self, transform: Matrix) {
+        self.base.set_transform(transform);
+        self.update_children();
+    }
+
+    fn set_end_transform(&mut self, end: Option<Matrix>) {
+        self.base.set_end_transform(end);
+        self.update_children();
+    }
+
+    fn set_parents(&mut self, parents: Vec<Motion>) {
+        self.base.set_parents(parents);
+        self.update_children();
+    }
+
+    fn set_triangle_test(&mut self, test: TriangleTest) {
+        for child in self.children.iter_mut() {
+            child.set_triangle_test(test);
+        }
+    }
+
+    fn includes(&self, other: &dyn Shape) -> bool {
+        same_shape(self, other) || self.children.iter().any(|child| child.includes(other))
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        let (bounds, child_bounds) = self.cached_bounds();
+        if !bounds.intersects(local_ray) {
+            return intersections(vec![]);
+        }
+        let xs = self
+            .children
+            .iter()
+            .zip(child_bounds)
+            .filter(|(_, child_bounds)| child_bounds.intersects(local_ray))
+            .flat_map(|(child, _)| child.intersect(local_ray))
+            .collect();
+        intersections(xs)
+    }
+
+    fn local_hits_before(&self, local_ray: &Ray, max_t: f64) -> bool {
+        let (bounds, child_bounds) = self.cached_bounds();
+        bounds.intersects(local_ray)
+            && self
+                .children
+                .iter()
+                .zip(child_bounds)
+                .filter(|(_, child_bounds)| child_bounds.intersects(local_ray))
+                .any(|(child, _)| child.hits_before(local_ray, max_t))
+    }
+
+    fn local_normal_at(&self, _local_point: Point, _hit: &Intersection) -> Vector {
+        panic!("a group has no surface of its own: normals come from its children")
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.cached_bounds().0
+    }
+}
+
+pub fn group() -> Group {
+    Group::default()
+}
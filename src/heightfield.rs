@@ -0,0 +1,414 @@
+use crate::canvas::Canvas;
+use crate::f64_helpers::EPS;
+use crate::intersection::{intersection, intersections, Intersection, Intersections};
+use crate::ray::Ray;
+use crate::shape::{Shape, ShapeBase};
+use crate::triangle::{moller_trumbore, watertight, TriangleTest};
+use crate::tuple::{aabb, check_slab, point, vector, Aabb, Point, Vector};
+
+#[cfg(test)]
+mod tests_for_heightfield {
+    use crate::canvas::canvas;
+    use crate::color::{color, WHITE};
+    use crate::heightfield::*;
+    use crate::intersection::intersection;
+    use crate::ray::ray;
+    use crate::shape::same_shape;
+    use crate::transform::scaling;
+    use crate::triangle::TriangleTest;
+
+    fn flat(height: f64) -> Heightfield {
+        heightfield(3, 3, vec![height; 9])
+    }
+
+    // a single peak of height 1 in the middle of a 3x3 grid
+    fn peak() -> Heightfield {
+        let mut heights = vec![0.; 9];
+        heights[4] = 1.;
+        heightfield(3, 3, heights)
+    }
+
+    #[test]
+    fn it_spans_the_unit_square_in_x_and_z() {
+        let h = peak();
+        assert_eq!(h.vertex(0, 0), point(0., 0., 0.));
+        assert_eq!(h.vertex(1, 1), point(0.5, 1., 0.5));
+        assert_eq!(h.vertex(2, 2), point(1., 0., 1.));
+    }
+
+    #[test]
+    fn a_vertical_ray_hits_a_flat_field_at_its_height() {
+        let h = flat(0.25);
+        let r = ray(point(0.3, 5., 0.7), vector(0., -1., 0.));
+        let xs = h.local_intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_abs_diff_eq!(xs[0].t, 4.75, epsilon = EPS);
+        assert!(same_shape(xs[0].object, &h));
+    }
+
+    #[test]
+    fn a_ray_outside_the_grid_misses() {
+        let h = flat(0.25);
+        let r = ray(point(1.5, 5., 0.5), vector(0., -1., 0.));
+        assert!(h.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn a_ray_above_the_highest_point_misses() {
+        let h = peak();
+        let r = ray(point(-1., 1.5, 0.5), vector(1., 0., 0.));
+        assert!(h.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn a_horizontal_ray_walks_across_cells_to_hit_the_peak() {
+        let h = peak();
+        let r = ray(point(-1., 0.5, 0.5), vector(1., 0., 0.));
+        let xs = h.local_intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_abs_diff_eq!(xs[0].t, 1.25, epsilon = EPS);
+        assert_abs_diff_eq!(xs[1].t, 1.75, epsilon = EPS);
+    }
+
+    #[test]
+    fn a_diagonal_ray_hits_the_peak() {
+        let h = peak();
+        let r = ray(point(0.5, 2., 0.5), vector(0.1, -1., 0.2));
+        let xs = h.local_intersect(&r);
+        let hit = r.position(xs.hit().unwrap().t);
+        assert_abs_diff_eq!(hit.y, h.height_at(hit.x, hit.z), epsilon = EPS);
+    }
+
+    #[test]
+    fn a_flat_field_faces_up() {
+        let h = flat(0.25);
+        let n = h.local_normal_at(point(0.3, 0.25, 0.7), &intersection(0., &h));
+        assert_eq!(n, vector(0., 1., 0.));
+    }
+
+    #[test]
+    fn its_normals_are_smooth_across_a_slope() {
+        let h = peak();
+        let i = intersection(0., &h);
+        let at_peak = h.local_normal_at(point(0.5, 1., 0.5), &i);
+        assert_eq!(at_peak, vector(0., 1., 0.));
+        // halfway down one side the normal leans away from the peak
+        let side = h.local_normal_at(point(0.25, 0.5, 0.5), &i);
+        assert!(side.x < 0.);
+        assert_abs_diff_eq!(side.z, 0., epsilon = EPS);
+    }
+
+    #[test]
+    fn it_can_be_built_from_a_greyscale_canvas() {
+        let mut c = canvas(2, 3);
+        c.write_pixel(1, 2, WHITE);
+        c.write_pixel(0, 1, color(0.5, 0.5, 0.5));
+        let h = heightfield_from_canvas(&c);
+        assert_eq!(h.columns(), 2);
+        assert_eq!(h.rows(), 3);
+        assert_abs_diff_eq!(h.vertex(1, 2).y, 1.);
+        assert_abs_diff_eq!(h.vertex(0, 1).y, 0.5);
+        assert_abs_diff_eq!(h.vertex(0, 0).y, 0.);
+    }
+
+    #[test]
+    fn rays_along_shared_edges_never_slip_through_a_watertight_field() {
+        let mut h = peak();
+        assert_eq!(h.triangle_test(), TriangleTest::MollerTrumbore);
+        h.set_triangle_test(TriangleTest::Watertight);
+        assert_eq!(h.triangle_test(), TriangleTest::Watertight);
+        for i in 1..100 {
+            let s = i as f64 / 100.;
+            // the diagonal splitting each cell, and the edges between cells
+            for &(x, z) in [(s, s), (0.5, s), (s, 0.5)].iter() {
+                let target = point(x, h.height_at(x, z), z);
+                let direction = vector(0.013, -1., 0.007);
+                let r = ray(target - direction * 5., direction);
+                assert!(!h.local_intersect(&r).is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn it_can_be_scaled_into_a_terrain_tile() {
+        let mut h = flat(0.5);
+        h.set_transform(scaling(100., 10., 100.));
+        let r = ray(point(30., 50., 60.), vector(0., -1., 0.));
+        let xs = h.intersect(&r);
+        assert_abs_diff_eq!(xs[0].t, 45., epsilon = EPS);
+    }
+}
+
+/// Terrain from a regular grid of heights.
+///
+/// Vertices are spread evenly over the unit square in x and z with their
+/// heights in y; scale with the transform to get real-world sizes. Each
+/// grid cell is split into two triangles whose normals are blended from
+/// normals at the vertices, so the terrain shades smoothly.
+#[derive(Debug, Clone)]
+pub struct Heightfield {
+    base: ShapeBase,
+    columns: usize,
+    rows: usize,
+    heights: Vec<f64>,
+    normals: Vec<Vector>,
+    min_height: f64,
+    max_height: f64,
+    triangle_test: TriangleTest,
+}
+
+impl Heightfield {
+    pub fn triangle_test(&self) -> TriangleTest {
+        self.triangle_test
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    fn cell_width(&self) -> f64 {
+        1. / (self.columns - 1) as f64
+    }
+
+    fn cell_depth(&self) -> f64 {
+        1. / (self.rows - 1) as f64
+    }
+
+    fn height(&self, column: usize, row: usize) -> f64 {
+        self.heights[row * self.columns + column]
+    }
+
+    pub fn vertex(&self, column: usize, row: usize) -> Point {
+        point(
+            column as f64 * self.cell_width(),
+            self.height(column, row),
+            row as f64 * self.cell_depth(),
+        )
+    }
+
+    fn vertex_normal(&self, column: usize, row: usize) -> Vector {
+        self.normals[row * self.columns + column]
+    }
+
+    /// The cell containing `x`, `z` and how far across it they are, 0 to 1.
+    fn cell_at(&self, x: f64, z: f64) -> (usize, usize, f64, f64) {
+        let gx = (x / self.cell_width()).clamp(0., (self.columns - 1) as f64);
+        let gz = (z / self.cell_depth()).clamp(0., (self.rows - 1) as f64);
+        let column = (gx.floor() as usize).min(self.columns - 2);
+        let row = (gz.floor() as usize).min(self.rows - 2);
+        (column, row, gx - column as f64, gz - row as f64)
+    }
+
+    /// Blends the values at the corners of the triangle under `x`, `z`.
+    ///
+    /// Cells are split along the diagonal from their (0, 0) to (1, 1)
+    /// corner, matching the triangles we intersect.
+    fn interpolate<T, F>(&self, x: f64, z: f64, value: F) -> T
+    where
+        T: std::ops::Add<Output = T> + std::ops::Mul<f64, Output = T>,
+        F: Fn(usize, usize) -> T,
+    {
+        let (c, r, fx, fz) = self.cell_at(x, z);
+        if fx >= fz {
+            value(c, r) * (1. - fx) + value(c + 1, r) * (fx - fz) + value(c + 1, r + 1) * fz
+        } else {
+            value(c, r) * (1. - fz) + value(c + 1, r + 1) * fx + value(c, r + 1) * (fz - fx)
+        }
+    }
+
+    pub fn height_at(&self, x: f64, z: f64) -> f64 {
+        self.interpolate(x, z, |c, r| self.height(c, r))
+    }
+
+    fn intersect_cell(&self, local_ray: &Ray, column: usize, row: usize, ts: &mut Vec<f64>) {
+        let p00 = self.vertex(column, row);
+        let p10 = self.vertex(column + 1, row);
+        let p11 = self.vertex(column + 1, row + 1);
+        let p01 = self.vertex(column, row + 1);
+        for &(a, b, c) in [(p00, p10, p11), (p00, p11, p01)].iter() {
+            let hit = match self.triangle_test {
+                TriangleTest::MollerTrumbore => moller_trumbore(local_ray, a, b - a, c - a),
+                TriangleTest::Watertight => watertight(local_ray, a, b, c),
+            };
+            if let Some((t, _, _)) = hit {
+                ts.push(t);
+            }
+        }
+    }
+}
+
+impl Shape for Heightfield {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    // Clip the ray to the bounding box, then walk only the cells it passes
+    // over (a 2D DDA in x and z) rather than testing every triangle.
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        let o = local_ray.origin;
+        let d = local_ray.direction;
+        let (xtmin, xtmax) = check_slab(o.x, d.x, 0., 1.);
+        let (ytmin, ytmax) = check_slab(o.y, d.y, self.min_height, self.max_height);
+        let (ztmin, ztmax) = check_slab(o.z, d.z, 0., 1.);
+        let tmin = xtmin.max(ytmin).max(ztmin);
+        let tmax = xtmax.min(ytmax).min(ztmax);
+        if tmin > tmax + EPS {
+            return intersections(vec![]);
+        }
+
+        let entry = local_ray.position(tmin);
+        let (mut column, mut row, fx, fz) = self.cell_at(entry.x, entry.z);
+        let mut ts = vec![];
+        // an entry on the boundary between cells touches the cells beside it
+        // too, which the walk below may head straight away from
+        let beside = |cell: usize, f: f64, cells: usize| {
+            let before = (f < EPS && cell > 0).then(|| cell - 1);
+            let after = (f > 1. - EPS && cell + 1 < cells).then(|| cell + 1);
+            before.into_iter().chain(Some(cell)).chain(after)
+        };
+        for c in beside(column, fx, self.columns - 1) {
+            for r in beside(row, fz, self.rows - 1) {
+                if (c, r) != (column, row) {
+                    self.intersect_cell(local_ray, c, r, &mut ts);
+                }
+            }
+        }
+        let (step_x, mut t_max_x, t_delta_x) = dda_axis(o.x, d.x, column, self.cell_width());
+        let (step_z, mut t_max_z, t_delta_z) = dda_axis(o.z, d.z, row, self.cell_depth());
+        loop {
+            self.intersect_cell(local_ray, column, row, &mut ts);
+            if t_max_x < t_max_z {
+                if t_max_x > tmax {
+                    break;
+                }
+                t_max_x += t_delta_x;
+                column = match step(column, step_x, self.columns - 1) {
+                    Some(c) => c,
+                    None => break,
+                };
+            } else {
+                if t_max_z > tmax {
+                    break;
+                }
+                t_max_z += t_delta_z;
+                row = match step(row, step_z, self.rows - 1) {
+                    Some(r) => r,
+                    None => break,
+                };
+            }
+        }
+
+        // a ray through a shared edge hits the triangles either side of it
+        ts.sort_by(|a, b| a.total_cmp(b));
+        ts.dedup_by(|a, b| (*a - *b).abs() < EPS);
+        intersections(ts.into_iter().map(|t| intersection(t, self)).collect())
+    }
+
+    fn set_triangle_test(&mut self, test: TriangleTest) {
+        self.triangle_test = test;
+    }
+
+    fn local_normal_at(&self, local_point: Point, _hit: &Intersection) -> Vector {
+        self.interpolate(local_point.x, local_point.z, |c, r| {
+            self.vertex_normal(c, r)
+        })
+        .normalize()
+    }
+
+    fn bounds(&self) -> Aabb {
+        aabb(
+            point(0., self.min_height, 0.),
+            point(1., self.max_height, 1.),
+        )
+    }
+}
+
+/// The DDA state along one axis: which way to step between cells, the `t`
+/// at which we cross into the next cell, and the `t` taken to cross a cell.
+fn dda_axis(origin: f64, direction: f64, cell: usize, cell_size: f64) -> (isize, f64, f64) {
+    if direction > 0. {
+        let boundary = (cell + 1) as f64 * cell_size;
+        (1, (boundary - origin) / direction, cell_size / direction)
+    } else if direction < 0. {
+        let boundary = cell as f64 * cell_size;
+        (-1, (boundary - origin) / direction, -cell_size / direction)
+    } else {
+        (0, f64::INFINITY, f64::INFINITY)
+    }
+}
+
+/// Moves one cell in `direction`, or `None` when that leaves the grid.
+fn step(cell: usize, direction: isize, cells: usize) -> Option<usize> {
+    let next = cell as isize + direction;
+    if next < 0 || next >= cells as isize {
+        None
+    } else {
+        Some(next as usize)
+    }
+}
+
+/// A heightfield of `columns` by `rows` heights, given a row at a time.
+pub fn heightfield(columns: usize, rows: usize, heights: Vec<f64>) -> Heightfield {
+    if columns < 2 || rows < 2 {
+        panic!(
+            "a heightfield needs at least 2x2 heights, not {}x{}",
+            columns, rows
+        );
+    }
+    if heights.len() != columns * rows {
+        panic!(
+            "expected {} heights for a {}x{} heightfield, found {}",
+            columns * rows,
+            columns,
+            rows,
+            heights.len()
+        );
+    }
+    let min_height = heights.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max_height = heights.iter().cloned().fold(-f64::INFINITY, f64::max);
+    let mut h = Heightfield {
+        base: ShapeBase::default(),
+        columns,
+        rows,
+        heights,
+        normals: vec![],
+        min_height,
+        max_height,
+        triangle_test: TriangleTest::default(),
+    };
+    // Vertex normals from central differences of the neighbouring heights,
+    // falling back to one-sided differences along the edges.
+    h.normals = (0..rows)
+        .flat_map(|r| (0..columns).map(move |c| (c, r)))
+        .map(|(c, r)| {
+            let (left, right) = (c.saturating_sub(1), (c + 1).min(columns - 1));
+            let (back, front) = (r.saturating_sub(1), (r + 1).min(rows - 1));
+            let dx =
+                (h.height(right, r) - h.height(left, r)) / ((right - left) as f64 * h.cell_width());
+            let dz =
+                (h.height(c, front) - h.height(c, back)) / ((front - back) as f64 * h.cell_depth());
+            vector(-dx, 1., -dz).normalize()
+        })
+        .collect();
+    h
+}
+
+/// A heightfield with one height per pixel, taken from its brightness.
+pub fn heightfield_from_canvas(canvas: &Canvas) -> Heightfield {
+    let mut heights = Vec::with_capacity(canvas.width() * canvas.height());
+    for row in 0..canvas.height() {
+        for column in 0..canvas.width() {
+            let pixel = canvas[column][row];
+            heights.push((pixel.red + pixel.green + pixel.blue) / 3.);
+        }
+    }
+    heightfield(canvas.width(), canvas.height(), heights)
+}
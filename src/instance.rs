@@ -0,0 +1,254 @@
+use crate::intersection::{intersection_with_normal, intersections, Intersection, Intersections};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::shape::{Shape, ShapeBase};
+use crate::tuple::{Aabb, Point, Vector};
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests_for_instance {
+    use crate::bvh::bvh;
+    use crate::color::{color, WHITE};
+    use crate::group::group;
+    use crate::instance::*;
+    use crate::light::point_light;
+    use crate::material::material;
+    use crate::ray::ray;
+    use crate::shape::same_shape;
+    use crate::sphere::sphere;
+    use crate::transform::{rotation_z, scaling, translation};
+    use crate::tuple::{point, vector};
+    use crate::world::{world, World};
+    use std::f64::consts::PI;
+
+    fn shared_sphere() -> Arc<dyn Shape> {
+        Arc::new(sphere())
+    }
+
+    #[test]
+    fn instances_share_their_geometry() {
+        let geometry = shared_sphere();
+        let mut a = instance(geometry.clone());
+        a.set_transform(translation(-3., 0., 0.));
+        let mut b = instance(geometry.clone());
+        b.set_transform(translation(3., 0., 0.));
+        assert_eq!(Arc::strong_count(&geometry), 3);
+        let r = ray(point(-3., 0., -5.), vector(0., 0., 1.));
+        assert_eq!(a.intersect(&r).len(), 2);
+        assert!(b.intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn its_hits_are_on_the_instance_itself() {
+        let i = instance(shared_sphere());
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let xs = i.intersect(&r);
+        assert!(same_shape(xs[0].object, &i));
+        assert!(!same_shape(xs[0].object, i.geometry()));
+    }
+
+    #[test]
+    fn its_normals_go_through_its_inverse_transpose() {
+        let transform = scaling(1., 0.5, 1.) * rotation_z(PI / 5.);
+        let mut i = instance(shared_sphere());
+        i.set_transform(transform);
+        let mut s = sphere();
+        s.set_transform(transform);
+        let r = ray(point(0., 2., -5.), vector(0., -0.3, 1.));
+        let xs = i.intersect(&r);
+        let expected = s.intersect(&r);
+        let p = r.position(xs[0].t);
+        assert_abs_diff_eq!(xs[0].t, expected[0].t);
+        assert_eq!(
+            xs[0].object.normal_at(p, &xs[0]),
+            s.normal_at(p, &expected[0])
+        );
+    }
+
+    #[test]
+    fn nested_transforms_inside_the_geometry_are_kept() {
+        let mut s = sphere();
+        s.set_transform(translation(0., 0., 2.));
+        let mut g = group();
+        g.add_child(Box::new(s));
+        g.set_transform(scaling(2., 2., 2.));
+        let mut i = instance(Arc::new(g));
+        i.set_transform(translation(10., 0., 0.));
+        let r = ray(point(10., 0., -10.), vector(0., 0., 1.));
+        let xs = i.intersect(&r);
+        assert_abs_diff_eq!(xs[0].t, 12.);
+        let n = xs[0].object.normal_at(r.position(xs[0].t), &xs[0]);
+        assert_eq!(n, vector(0., 0., -1.));
+    }
+
+    fn red_sphere() -> Arc<dyn Shape> {
+        let mut s = sphere();
+        s.set_material(Material {
+            color: color(1., 0., 0.),
+            ambient: 1.,
+            diffuse: 0.,
+            specular: 0.,
+            ..material()
+        });
+        Arc::new(s)
+    }
+
+    fn world_with(i: Instance) -> World {
+        let mut w = world();
+        w.objects.push(Box::new(i));
+        w.lights.push(point_light(point(0., 0., -10.), WHITE));
+        w
+    }
+
+    #[test]
+    fn it_is_shaded_with_the_material_of_the_geometry_it_hits() {
+        let i = instance(red_sphere());
+        assert_eq!(i.material().color, color(1., 0., 0.));
+        let w = world_with(i);
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        assert_eq!(w.color_at(&r), color(1., 0., 0.));
+    }
+
+    #[test]
+    fn its_own_material_overrides_the_geometrys() {
+        let mut i = instance(red_sphere());
+        let blue = Material {
+            color: color(0., 0., 1.),
+            ambient: 1.,
+            diffuse: 0.,
+            specular: 0.,
+            ..material()
+        };
+        i.set_material(blue);
+        assert_eq!(*i.material(), blue);
+        let w = world_with(i.clone());
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        assert_eq!(w.color_at(&r), color(0., 0., 1.));
+        i.clear_material();
+        let w = world_with(i);
+        assert_eq!(w.color_at(&r), color(1., 0., 0.));
+    }
+
+    #[test]
+    fn its_bounds_include_both_transforms() {
+        let mut s = sphere();
+        s.set_transform(scaling(2., 2., 2.));
+        let mut i = instance(Arc::new(s));
+        i.set_transform(translation(5., 0., 0.));
+        assert_eq!(i.bounds().min, point(-2., -2., -2.));
+        assert_eq!(i.parent_space_bounds().min, point(3., -2., -2.));
+        assert_eq!(i.parent_space_bounds().max, point(7., 2., 2.));
+    }
+
+    #[test]
+    fn a_forest_of_instances_can_go_in_a_bvh() {
+        let tree = shared_sphere();
+        let mut forest: Vec<Box<dyn Shape>> = vec![];
+        for x in 0..100 {
+            let mut i = instance(tree.clone());
+            i.set_transform(translation(x as f64 * 3., 0., 0.));
+            forest.push(Box::new(i));
+        }
+        let b = bvh(forest);
+        let r = ray(point(-5., 0., 0.), vector(1., 0., 0.));
+        let xs = b.intersect(&r);
+        assert_eq!(xs.len(), 200);
+        let n = xs[0].object.normal_at(r.position(xs[0].t), &xs[0]);
+        assert_eq!(n, vector(-1., 0., 0.));
+        assert!(b.hits_before(&r, 5.));
+        assert!(!b.hits_before(&r, 3.));
+    }
+}
+
+/// A placement of shared geometry with its own transform, so the same mesh
+/// can appear many times while only being stored once.
+///
+/// The geometry may be used by many instances at once, so nothing inside it
+/// knows where it has been placed. Instead the instance works out the normal
+/// while intersecting and reports every hit as being on itself, which lets
+/// the usual transform handling carry the normal out to the world.
+///
+/// Hits are shaded with the material of whatever part of the geometry was
+/// hit, unless the instance has been given a material of its own.
+#[derive(Debug, Clone)]
+pub struct Instance {
+    base: ShapeBase,
+    geometry: Arc<dyn Shape>,
+    material: Option<Material>,
+}
+
+impl Instance {
+    pub fn geometry(&self) -> &dyn Shape {
+        self.geometry.as_ref()
+    }
+
+    /// Goes back to shading with the geometry's own materials.
+    pub fn clear_material(&mut self) {
+        self.material = None;
+    }
+}
+
+impl Shape for Instance {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    fn material(&self) -> &Material {
+        self.material
+            .as_ref()
+            .unwrap_or_else(|| self.geometry.material())
+    }
+
+    fn set_material(&mut self, material: Material) {
+        self.material = Some(material);
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        let xs = self
+            .geometry
+            .intersect(local_ray)
+            .into_iter()
+            .map(|i| {
+                let normal = i.object.normal_at(local_ray.position(i.t), &i);
+                let material = self
+                    .material
+                    .as_ref()
+                    .unwrap_or_else(|| i.material.unwrap_or(i.object.material()));
+                Intersection {
+                    u: i.u,
+                    v: i.v,
+                    material: Some(material),
+                    ..intersection_with_normal(i.t, self, normal)
+                }
+            })
+            .collect();
+        intersections(xs)
+    }
+
+    fn local_hits_before(&self, local_ray: &Ray, max_t: f64) -> bool {
+        self.geometry.hits_before(local_ray, max_t)
+    }
+
+    fn local_normal_at(&self, _local_point: Point, hit: &Intersection) -> Vector {
+        match hit.normal {
+            Some(normal) => normal,
+            None => panic!("instance hits carry their normal from intersection"),
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.geometry.parent_space_bounds()
+    }
+}
+
+pub fn instance(geometry: Arc<dyn Shape>) -> Instance {
+    Instance {
+        base: ShapeBase::default(),
+        geometry,
+        material: None,
+    }
+}
@@ -0,0 +1,321 @@
+use crate::f64_helpers::EPS;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::shape::{same_shape, Shape};
+use crate::tuple::{Point, Vector};
+use std::ops::Index;
+
+#[cfg(test)]
+mod tests_for_intersection {
+    use crate::color::color;
+    use crate::intersection::*;
+    use crate::material::material;
+    use crate::plane::plane;
+    use crate::ray::ray;
+    use crate::shape::same_shape;
+    use crate::sphere::sphere;
+    use crate::transform::translation;
+    use crate::tuple::{point, vector};
+    use std::f64::consts::{FRAC_1_SQRT_2, SQRT_2};
+
+    #[test]
+    fn it_encapsulates_t_and_object() {
+        let s = sphere();
+        let i = intersection(3.5, &s);
+        assert_abs_diff_eq!(i.t, 3.5);
+        assert!(same_shape(i.object, &s));
+    }
+
+    #[test]
+    fn it_can_carry_u_and_v() {
+        let s = sphere();
+        let i = intersection_with_uv(3.5, &s, 0.2, 0.4);
+        assert_abs_diff_eq!(i.u, 0.2);
+        assert_abs_diff_eq!(i.v, 0.4);
+    }
+
+    #[test]
+    fn it_can_carry_a_normal() {
+        let s = sphere();
+        assert_eq!(intersection(3.5, &s).normal, None);
+        let i = intersection_with_normal(3.5, &s, vector(0., 1., 0.));
+        assert_eq!(i.normal, Some(vector(0., 1., 0.)));
+    }
+
+    #[test]
+    fn precomputing_the_state_of_a_hit() {
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let s = sphere();
+        let comps = intersection(4., &s).prepare_computations(&r);
+        assert_abs_diff_eq!(comps.t, 4.);
+        assert!(same_shape(comps.object, &s));
+        assert_eq!(comps.point, point(0., 0., -1.));
+        assert_eq!(comps.eyev, vector(0., 0., -1.));
+        assert_eq!(comps.normalv, vector(0., 0., -1.));
+        assert!(!comps.inside);
+    }
+
+    #[test]
+    fn precomputing_the_reflection_vector() {
+        let p = plane();
+        let r = ray(
+            point(0., 1., -1.),
+            vector(0., -FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+        );
+        let comps = intersection(SQRT_2, &p).prepare_computations(&r);
+        assert_eq!(comps.reflectv, vector(0., FRAC_1_SQRT_2, FRAC_1_SQRT_2));
+    }
+
+    #[test]
+    fn a_hit_from_inside_flips_the_normal() {
+        let r = ray(point(0., 0., 0.), vector(0., 0., 1.));
+        let s = sphere();
+        let comps = intersection(1., &s).prepare_computations(&r);
+        assert_eq!(comps.point, point(0., 0., 1.));
+        assert_eq!(comps.eyev, vector(0., 0., -1.));
+        assert!(comps.inside);
+        assert_eq!(comps.normalv, vector(0., 0., -1.));
+    }
+
+    #[test]
+    fn the_over_point_is_just_above_the_surface() {
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let mut s = sphere();
+        s.set_transform(translation(0., 0., 1.));
+        let comps = intersection(5., &s).prepare_computations(&r);
+        assert!(comps.over_point.z < -EPS / 2.);
+        assert!(comps.point.z > comps.over_point.z);
+    }
+
+    #[test]
+    fn the_computations_use_the_material_carried_by_the_hit() {
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let s = sphere();
+        let comps = intersection(4., &s).prepare_computations(&r);
+        assert_eq!(*comps.material, *s.material());
+        let red = Material {
+            color: color(1., 0., 0.),
+            ..material()
+        };
+        let i = Intersection {
+            material: Some(&red),
+            ..intersection(4., &s)
+        };
+        assert_eq!(*i.prepare_computations(&r).material, red);
+    }
+
+    #[test]
+    fn they_can_be_aggregated() {
+        let s = sphere();
+        let i1 = intersection(1., &s);
+        let i2 = intersection(2., &s);
+        let xs = intersections(vec![i1, i2]);
+        assert_eq!(xs.len(), 2);
+        assert_abs_diff_eq!(xs[0].t, 1.);
+        assert_abs_diff_eq!(xs[1].t, 2.);
+    }
+
+    #[test]
+    fn they_are_kept_sorted_by_t() {
+        let s = sphere();
+        let xs = intersections(vec![
+            intersection(5., &s),
+            intersection(7., &s),
+            intersection(-3., &s),
+            intersection(2., &s),
+        ]);
+        let ts: Vec<f64> = xs.iter().map(|i| i.t).collect();
+        assert_eq!(ts, vec![-3., 2., 5., 7.]);
+    }
+
+    #[test]
+    fn the_hit_when_all_have_positive_t() {
+        let s = sphere();
+        let i1 = intersection(1., &s);
+        let i2 = intersection(2., &s);
+        let xs = intersections(vec![i2, i1]);
+        assert_eq!(xs.hit(), Some(&i1));
+    }
+
+    #[test]
+    fn the_hit_when_some_have_negative_t() {
+        let s = sphere();
+        let i1 = intersection(-1., &s);
+        let i2 = intersection(1., &s);
+        let xs = intersections(vec![i2, i1]);
+        assert_eq!(xs.hit(), Some(&i2));
+    }
+
+    #[test]
+    fn the_hit_when_all_have_negative_t() {
+        let s = sphere();
+        let i1 = intersection(-2., &s);
+        let i2 = intersection(-1., &s);
+        let xs = intersections(vec![i2, i1]);
+        assert_eq!(xs.hit(), None);
+    }
+
+    #[test]
+    fn the_hit_is_always_the_lowest_nonnegative_intersection() {
+        let s = sphere();
+        let i1 = intersection(5., &s);
+        let i2 = intersection(7., &s);
+        let i3 = intersection(-3., &s);
+        let i4 = intersection(2., &s);
+        let xs = intersections(vec![i1, i2, i3, i4]);
+        assert_eq!(xs.hit(), Some(&i4));
+    }
+
+    #[test]
+    fn intersections_with_different_objects_are_not_equal() {
+        let s1 = sphere();
+        let s2 = sphere();
+        assert!(intersection(1., &s1) != intersection(1., &s2));
+        assert!(intersection(1., &s1) == intersection(1., &s1));
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Intersection<'a> {
+    pub t: f64,
+    pub object: &'a dyn Shape,
+    // Barycentric coordinates of the hit, only meaningful for triangles.
+    pub u: f64,
+    pub v: f64,
+    // The object-space normal, for shapes that work it out while
+    // intersecting and could not easily recover it from the point later.
+    pub normal: Option<Vector>,
+    // The time of the ray, so normals are found where a moving shape was.
+    pub time: f64,
+    // The material to shade the hit with, for shapes such as instances whose
+    // hits are reported on a different object than the one that was hit.
+    // `None` means the object's own material.
+    pub material: Option<&'a Material>,
+}
+
+impl<'a> Intersection<'a> {
+    /// Works out everything shading needs to know about this hit by `ray`.
+    pub fn prepare_computations(&self, ray: &Ray) -> Computations<'a> {
+        let point = ray.position(self.t);
+        let eyev = -ray.direction;
+        let mut normalv = self.object.normal_at(point, self);
+        let inside = normalv.dot(eyev) < 0.;
+        if inside {
+            normalv = -normalv;
+        }
+        Computations {
+            t: self.t,
+            object: self.object,
+            point,
+            over_point: point + normalv * EPS,
+            eyev,
+            normalv,
+            reflectv: ray.direction.reflect(normalv),
+            inside,
+            time: self.time,
+            material: self.material.unwrap_or(self.object.material()),
+        }
+    }
+}
+
+impl<'a> PartialEq for Intersection<'a> {
+    fn eq(&self, other: &Intersection<'a>) -> bool {
+        self.t == other.t && same_shape(self.object, other.object)
+    }
+}
+
+/// A collection of intersections, always kept sorted by increasing `t`.
+#[derive(Debug, Clone, Default)]
+pub struct Intersections<'a>(Vec<Intersection<'a>>);
+
+impl<'a> Intersections<'a> {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Intersection<'a>> {
+        self.0.iter()
+    }
+
+    /// Stamps every intersection with the time of the ray that found it.
+    pub fn at_time(mut self, time: f64) -> Intersections<'a> {
+        for i in self.0.iter_mut() {
+            i.time = time;
+        }
+        self
+    }
+
+    /// The visible intersection: the one with the lowest non-negative `t`.
+    pub fn hit(&self) -> Option<&Intersection<'a>> {
+        self.0.iter().find(|i| i.t >= 0.)
+    }
+}
+
+impl<'a> Index<usize> for Intersections<'a> {
+    type Output = Intersection<'a>;
+
+    fn index(&self, index: usize) -> &Intersection<'a> {
+        &self.0[index]
+    }
+}
+
+impl<'a> IntoIterator for Intersections<'a> {
+    type Item = Intersection<'a>;
+    type IntoIter = std::vec::IntoIter<Intersection<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// A hit together with the vectors needed to shade it.
+///
+/// `normalv` always faces the eye, flipped if the hit was on the inside of
+/// the object. `over_point` is nudged off the surface along it, so rays
+/// starting there do not hit the surface they start on through rounding.
+/// `reflectv` is the direction the ray bounces off in.
+#[derive(Debug, Copy, Clone)]
+pub struct Computations<'a> {
+    pub t: f64,
+    pub object: &'a dyn Shape,
+    pub point: Point,
+    pub over_point: Point,
+    pub eyev: Vector,
+    pub normalv: Vector,
+    pub reflectv: Vector,
+    pub inside: bool,
+    pub time: f64,
+    pub material: &'a Material,
+}
+
+pub fn intersection(t: f64, object: &dyn Shape) -> Intersection<'_> {
+    intersection_with_uv(t, object, 0., 0.)
+}
+
+pub fn intersection_with_uv(t: f64, object: &dyn Shape, u: f64, v: f64) -> Intersection<'_> {
+    Intersection {
+        t,
+        object,
+        u,
+        v,
+        normal: None,
+        time: 0.,
+        material: None,
+    }
+}
+
+pub fn intersection_with_normal(t: f64, object: &dyn Shape, normal: Vector) -> Intersection<'_> {
+    Intersection {
+        normal: Some(normal),
+        ..intersection(t, object)
+    }
+}
+
+pub fn intersections(mut xs: Vec<Intersection>) -> Intersections {
+    xs.sort_by(|a, b| a.t.total_cmp(&b.t));
+    Intersections(xs)
+}
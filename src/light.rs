@@ -0,0 +1,30 @@
+use crate::color::Color;
+use crate::tuple::Point;
+
+#[cfg(test)]
+mod tests_for_light {
+    use crate::color::WHITE;
+    use crate::light::*;
+    use crate::tuple::point;
+
+    #[test]
+    fn a_point_light_has_a_position_and_intensity() {
+        let light = point_light(point(0., 0., 0.), WHITE);
+        assert_eq!(light.position, point(0., 0., 0.));
+        assert_eq!(light.intensity, WHITE);
+    }
+}
+
+/// A light with no size, shining equally in every direction.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PointLight {
+    pub position: Point,
+    pub intensity: Color,
+}
+
+pub fn point_light(position: Point, intensity: Color) -> PointLight {
+    PointLight {
+        position,
+        intensity,
+    }
+}
@@ -1,10 +1,36 @@
 #[macro_use]
 extern crate approx;
 
+pub mod bvh;
+pub mod camera;
 pub mod canvas;
 pub mod color;
+pub mod cone;
+pub mod csg;
+pub mod cube;
+pub mod cylinder;
+pub mod disk;
 pub mod f64_helpers;
+pub mod group;
+pub mod heightfield;
+pub mod instance;
+pub mod intersection;
+pub mod light;
+pub mod material;
+pub mod matrix;
+pub mod plane;
+pub mod profile;
+pub mod quaternion;
+pub mod ray;
+pub mod sdf;
+pub mod shape;
+pub mod sphere;
+pub mod torus;
+pub mod transform;
+pub mod triangle;
 pub mod tuple;
+pub mod voxel;
+pub mod world;
 
 #[derive(Debug, Copy, Clone)]
 struct Projectile {
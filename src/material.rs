@@ -0,0 +1,171 @@
+use crate::color::{Color, BLACK, WHITE};
+use crate::f64_helpers::EPS;
+use crate::light::PointLight;
+use crate::tuple::{Point, Vector};
+use std::cmp::PartialEq;
+
+#[cfg(test)]
+mod tests_for_material {
+    use crate::color::color;
+    use crate::light::point_light;
+    use crate::material::*;
+    use crate::tuple::{point, vector};
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    #[test]
+    fn it_has_sensible_defaults() {
+        let m = material();
+        assert_eq!(m.color, WHITE);
+        assert_abs_diff_eq!(m.ambient, 0.1);
+        assert_abs_diff_eq!(m.diffuse, 0.9);
+        assert_abs_diff_eq!(m.specular, 0.9);
+        assert_abs_diff_eq!(m.shininess, 200.);
+        assert_abs_diff_eq!(m.reflective, 0.);
+    }
+
+    fn light_from(position: Point, eyev: Vector) -> Color {
+        let light = point_light(position, WHITE);
+        let normalv = vector(0., 0., -1.);
+        lighting(&material(), &light, point(0., 0., 0.), eyev, normalv, false)
+    }
+
+    #[test]
+    fn lighting_with_the_eye_between_the_light_and_the_surface() {
+        let c = light_from(point(0., 0., -10.), vector(0., 0., -1.));
+        assert_eq!(c, color(1.9, 1.9, 1.9));
+    }
+
+    #[test]
+    fn lighting_with_the_eye_offset_45_degrees() {
+        let c = light_from(
+            point(0., 0., -10.),
+            vector(0., FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+        );
+        assert_eq!(c, color(1., 1., 1.));
+    }
+
+    #[test]
+    fn lighting_with_the_light_offset_45_degrees() {
+        let c = light_from(point(0., 10., -10.), vector(0., 0., -1.));
+        assert_eq!(c, color(0.7364, 0.7364, 0.7364));
+    }
+
+    #[test]
+    fn lighting_with_the_eye_in_the_path_of_the_reflection() {
+        let c = light_from(
+            point(0., 10., -10.),
+            vector(0., -FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+        );
+        assert_eq!(c, color(1.6364, 1.6364, 1.6364));
+    }
+
+    #[test]
+    fn lighting_with_the_light_behind_the_surface() {
+        let c = light_from(point(0., 0., 10.), vector(0., 0., -1.));
+        assert_eq!(c, color(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn lighting_with_the_surface_in_shadow() {
+        let light = point_light(point(0., 0., -10.), WHITE);
+        let eyev = vector(0., 0., -1.);
+        let normalv = vector(0., 0., -1.);
+        let c = lighting(&material(), &light, point(0., 0., 0.), eyev, normalv, true);
+        assert_eq!(c, color(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn the_light_and_material_colors_are_blended() {
+        let m = Material {
+            color: color(1., 0.5, 0.),
+            ..material()
+        };
+        let light = point_light(point(0., 0., -10.), color(0.5, 0.5, 1.));
+        let c = lighting(
+            &m,
+            &light,
+            point(0., 0., 0.),
+            vector(0., 0., -1.),
+            vector(0., 0., -1.),
+            false,
+        );
+        // specular highlights take the light's color, not the surface's
+        assert_eq!(c, color(0.95, 0.7, 0.9));
+    }
+}
+
+/// How a surface responds to light, in the Phong reflection model: a flat
+/// `ambient` glow, `diffuse` light scattered evenly in all directions, and
+/// a `specular` highlight that tightens as `shininess` grows.
+///
+/// `reflective` runs from 0 for a matt surface to 1 for a perfect mirror.
+#[derive(Debug, Copy, Clone)]
+pub struct Material {
+    pub color: Color,
+    pub ambient: f64,
+    pub diffuse: f64,
+    pub specular: f64,
+    pub shininess: f64,
+    pub reflective: f64,
+}
+
+impl PartialEq for Material {
+    fn eq(&self, other: &Material) -> bool {
+        self.color == other.color
+            && abs_diff_eq!(self.ambient, other.ambient, epsilon = EPS)
+            && abs_diff_eq!(self.diffuse, other.diffuse, epsilon = EPS)
+            && abs_diff_eq!(self.specular, other.specular, epsilon = EPS)
+            && abs_diff_eq!(self.shininess, other.shininess, epsilon = EPS)
+            && abs_diff_eq!(self.reflective, other.reflective, epsilon = EPS)
+    }
+}
+// Should not derive Eq as our f64's could be NaN-y and NaN != NaN
+
+impl Default for Material {
+    fn default() -> Material {
+        material()
+    }
+}
+
+pub fn material() -> Material {
+    Material {
+        color: WHITE,
+        ambient: 0.1,
+        diffuse: 0.9,
+        specular: 0.9,
+        shininess: 200.,
+        reflective: 0.,
+    }
+}
+
+/// The color of `point` on a surface with normal `normalv`, lit by `light`
+/// and seen from the direction `eyev`. Both vectors must be normalized.
+/// Only ambient light reaches a point `in_shadow`.
+pub fn lighting(
+    material: &Material,
+    light: &PointLight,
+    point: Point,
+    eyev: Vector,
+    normalv: Vector,
+    in_shadow: bool,
+) -> Color {
+    let effective_color = material.color * light.intensity;
+    let ambient = effective_color * material.ambient;
+    if in_shadow {
+        return ambient;
+    }
+    let lightv = (light.position - point).normalize();
+    let light_dot_normal = lightv.dot(normalv);
+    if light_dot_normal < 0. {
+        // the light is on the other side of the surface
+        return ambient;
+    }
+    let diffuse = effective_color * material.diffuse * light_dot_normal;
+    let reflect_dot_eye = (-lightv).reflect(normalv).dot(eyev);
+    let specular = if reflect_dot_eye <= 0. {
+        BLACK
+    } else {
+        light.intensity * material.specular * reflect_dot_eye.powf(material.shininess)
+    };
+    ambient + diffuse + specular
+}
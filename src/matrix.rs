@@ -0,0 +1,534 @@
+use crate::f64_helpers::EPS;
+use crate::tuple::{point, vector, Point, Vector};
+use std::cmp::PartialEq;
+use std::ops::{Index, IndexMut, Mul};
+
+#[cfg(test)]
+mod tests_for_matrix {
+    use crate::matrix::*;
+    use crate::tuple::{point, vector};
+
+    #[test]
+    fn it_can_be_constructed_4x4_and_accessed_by_row_and_column() {
+        let m = matrix4([
+            [1., 2., 3., 4.],
+            [5.5, 6.5, 7.5, 8.5],
+            [9., 10., 11., 12.],
+            [13.5, 14.5, 15.5, 16.5],
+        ]);
+        assert_eq!(m.size(), 4);
+        assert!(m[(0, 0)] == 1.);
+        assert!(m[(0, 3)] == 4.);
+        assert!(m[(1, 0)] == 5.5);
+        assert!(m[(1, 2)] == 7.5);
+        assert!(m[(2, 2)] == 11.);
+        assert!(m[(3, 0)] == 13.5);
+        assert!(m[(3, 2)] == 15.5);
+    }
+
+    #[test]
+    fn it_can_be_constructed_2x2() {
+        let m = matrix2([[-3., 5.], [1., -2.]]);
+        assert_eq!(m.size(), 2);
+        assert!(m[(0, 0)] == -3.);
+        assert!(m[(0, 1)] == 5.);
+        assert!(m[(1, 0)] == 1.);
+        assert!(m[(1, 1)] == -2.);
+    }
+
+    #[test]
+    fn it_can_be_constructed_3x3() {
+        let m = matrix3([[-3., 5., 0.], [1., -2., -7.], [0., 1., 1.]]);
+        assert_eq!(m.size(), 3);
+        assert!(m[(0, 0)] == -3.);
+        assert!(m[(1, 1)] == -2.);
+        assert!(m[(2, 2)] == 1.);
+    }
+
+    #[test]
+    fn it_supports_comparison_equality() {
+        let a = matrix4([
+            [1., 2., 3., 4.],
+            [5., 6., 7., 8.],
+            [9., 8., 7., 6.],
+            [5., 4., 3., 2.],
+        ]);
+        let b = matrix4([
+            [1., 2., 3., 4.],
+            [5., 6., 7., 8.],
+            [9., 8., 7., 6.],
+            [5., 4., 3., 2. + EPS / 10.],
+        ]);
+        assert!(a == b);
+    }
+
+    #[test]
+    fn it_supports_comparison_inequality() {
+        let a = matrix4([
+            [1., 2., 3., 4.],
+            [5., 6., 7., 8.],
+            [9., 8., 7., 6.],
+            [5., 4., 3., 2.],
+        ]);
+        let b = matrix4([
+            [2., 3., 4., 5.],
+            [6., 7., 8., 9.],
+            [8., 7., 6., 5.],
+            [4., 3., 2., 1.],
+        ]);
+        assert!(a != b);
+        assert!(matrix2([[1., 0.], [0., 1.]]) != identity());
+    }
+
+    #[test]
+    fn it_can_be_multiplied_by_another_matrix() {
+        let a = matrix4([
+            [1., 2., 3., 4.],
+            [5., 6., 7., 8.],
+            [9., 8., 7., 6.],
+            [5., 4., 3., 2.],
+        ]);
+        let b = matrix4([
+            [-2., 1., 2., 3.],
+            [3., 2., 1., -1.],
+            [4., 3., 6., 5.],
+            [1., 2., 7., 8.],
+        ]);
+        let expected = matrix4([
+            [20., 22., 50., 48.],
+            [44., 54., 114., 108.],
+            [40., 58., 110., 102.],
+            [16., 26., 46., 42.],
+        ]);
+        assert_eq!(a * b, expected);
+    }
+
+    #[test]
+    fn it_can_multiply_a_point_applying_translation() {
+        let a = matrix4([
+            [1., 2., 3., 4.],
+            [2., 4., 4., 2.],
+            [8., 6., 4., 1.],
+            [0., 0., 0., 1.],
+        ]);
+        let p = point(1., 2., 3.);
+        assert_eq!(a * p, point(18., 24., 33.));
+    }
+
+    #[test]
+    fn it_can_multiply_a_vector_ignoring_translation() {
+        let a = matrix4([
+            [1., 2., 3., 4.],
+            [2., 4., 4., 2.],
+            [8., 6., 4., 1.],
+            [0., 0., 0., 1.],
+        ]);
+        let v = vector(1., 2., 3.);
+        assert_eq!(a * v, vector(14., 22., 32.));
+    }
+
+    #[test]
+    fn it_is_unchanged_by_the_identity() {
+        let a = matrix4([
+            [0., 1., 2., 4.],
+            [1., 2., 4., 8.],
+            [2., 4., 8., 16.],
+            [4., 8., 16., 32.],
+        ]);
+        assert_eq!(a * identity(), a);
+        assert_eq!(identity() * a, a);
+        assert_eq!(IDENTITY * point(1., 2., 3.), point(1., 2., 3.));
+        assert_eq!(IDENTITY * vector(1., 2., 3.), vector(1., 2., 3.));
+    }
+
+    #[test]
+    fn it_can_be_transposed() {
+        let a = matrix4([
+            [0., 9., 3., 0.],
+            [9., 8., 0., 8.],
+            [1., 8., 5., 3.],
+            [0., 0., 5., 8.],
+        ]);
+        let expected = matrix4([
+            [0., 9., 1., 0.],
+            [9., 8., 8., 0.],
+            [3., 0., 5., 5.],
+            [0., 8., 3., 8.],
+        ]);
+        assert_eq!(a.transpose(), expected);
+        assert_eq!(transpose(a), expected);
+        assert_eq!(identity().transpose(), identity());
+    }
+
+    #[test]
+    fn it_has_a_2x2_determinant() {
+        let a = matrix2([[1., 5.], [-3., 2.]]);
+        assert_abs_diff_eq!(a.determinant(), 17., epsilon = EPS);
+    }
+
+    #[test]
+    fn it_has_submatrices() {
+        let a = matrix3([[1., 5., 0.], [-3., 2., 7.], [0., 6., -3.]]);
+        assert_eq!(a.submatrix(0, 2), matrix2([[-3., 2.], [0., 6.]]));
+        let b = matrix4([
+            [-6., 1., 1., 6.],
+            [-8., 5., 8., 6.],
+            [-1., 0., 8., 2.],
+            [-7., 1., -1., 1.],
+        ]);
+        let expected = matrix3([[-6., 1., 6.], [-8., 8., 6.], [-7., -1., 1.]]);
+        assert_eq!(b.submatrix(2, 1), expected);
+    }
+
+    #[test]
+    fn it_has_minors_and_cofactors() {
+        let a = matrix3([[3., 5., 0.], [2., -1., -7.], [6., -1., 5.]]);
+        assert_abs_diff_eq!(a.submatrix(1, 0).determinant(), 25., epsilon = EPS);
+        assert_abs_diff_eq!(a.minor(1, 0), 25., epsilon = EPS);
+        assert_abs_diff_eq!(a.minor(0, 0), -12., epsilon = EPS);
+        assert_abs_diff_eq!(a.cofactor(0, 0), -12., epsilon = EPS);
+        assert_abs_diff_eq!(a.cofactor(1, 0), -25., epsilon = EPS);
+    }
+
+    #[test]
+    fn it_has_a_3x3_determinant() {
+        let a = matrix3([[1., 2., 6.], [-5., 8., -4.], [2., 6., 4.]]);
+        assert_abs_diff_eq!(a.cofactor(0, 0), 56., epsilon = EPS);
+        assert_abs_diff_eq!(a.cofactor(0, 1), 12., epsilon = EPS);
+        assert_abs_diff_eq!(a.cofactor(0, 2), -46., epsilon = EPS);
+        assert_abs_diff_eq!(a.determinant(), -196., epsilon = EPS);
+    }
+
+    #[test]
+    fn it_has_a_4x4_determinant() {
+        let a = matrix4([
+            [-2., -8., 3., 5.],
+            [-3., 1., 7., 3.],
+            [1., 2., -9., 6.],
+            [-6., 7., 7., -9.],
+        ]);
+        assert_abs_diff_eq!(a.cofactor(0, 0), 690., epsilon = EPS);
+        assert_abs_diff_eq!(a.cofactor(0, 1), 447., epsilon = EPS);
+        assert_abs_diff_eq!(a.cofactor(0, 2), 210., epsilon = EPS);
+        assert_abs_diff_eq!(a.cofactor(0, 3), 51., epsilon = EPS);
+        assert_abs_diff_eq!(a.determinant(), -4071., epsilon = EPS);
+        assert_abs_diff_eq!(determinant(a), -4071., epsilon = EPS);
+    }
+
+    #[test]
+    fn it_knows_whether_it_is_invertible() {
+        let a = matrix4([
+            [6., 4., 4., 4.],
+            [5., 5., 7., 6.],
+            [4., -9., 3., -7.],
+            [9., 1., 7., -6.],
+        ]);
+        assert!(a.is_invertible());
+        let b = matrix4([
+            [-4., 2., -2., -3.],
+            [9., 6., 2., 6.],
+            [0., -5., 1., -5.],
+            [0., 0., 0., 0.],
+        ]);
+        assert!(!b.is_invertible());
+    }
+
+    #[test]
+    fn it_can_be_inverted() {
+        let a = matrix4([
+            [-5., 2., 6., -8.],
+            [1., -5., 1., 8.],
+            [7., 7., -6., -7.],
+            [1., -3., 7., 4.],
+        ]);
+        let b = a.inverse();
+        assert_abs_diff_eq!(a.determinant(), 532., epsilon = EPS);
+        assert_abs_diff_eq!(a.cofactor(2, 3), -160., epsilon = EPS);
+        assert_abs_diff_eq!(b[(3, 2)], -160. / 532., epsilon = EPS);
+        let expected = matrix4([
+            [0.21805, 0.45113, 0.24060, -0.04511],
+            [-0.80827, -1.45677, -0.44361, 0.52068],
+            [-0.07895, -0.22368, -0.05263, 0.19737],
+            [-0.52256, -0.81391, -0.30075, 0.30639],
+        ]);
+        assert_eq!(b, expected);
+        assert_eq!(inverse(a), expected);
+    }
+
+    #[test]
+    fn it_undoes_a_product_when_multiplied_by_the_inverse() {
+        let a = matrix4([
+            [3., -9., 7., 3.],
+            [3., -8., 2., -9.],
+            [-4., 4., 4., 1.],
+            [-6., 5., -1., 1.],
+        ]);
+        let b = matrix4([
+            [8., 2., 2., 2.],
+            [3., -1., 7., 0.],
+            [7., 0., 5., 4.],
+            [6., -2., 0., 5.],
+        ]);
+        let c = a * b;
+        assert_eq!(c * b.inverse(), a);
+        assert_eq!(a * a.inverse(), identity());
+    }
+
+    #[test]
+    #[should_panic]
+    fn it_panics_when_inverting_a_singular_matrix() {
+        let a = matrix4([
+            [-4., 2., -2., -3.],
+            [9., 6., 2., 6.],
+            [0., -5., 1., -5.],
+            [0., 0., 0., 0.],
+        ]);
+        a.inverse();
+    }
+}
+
+const MAX_SIZE: usize = 4;
+
+/// A square matrix of up to 4x4.
+///
+/// Only the 4x4 form transforms `Point`s and `Vector`s; the 3x3 and 2x2 forms
+/// exist so we can compute determinants via submatrices and cofactors.
+#[derive(Debug, Copy, Clone)]
+pub struct Matrix {
+    size: usize,
+    elements: [[f64; MAX_SIZE]; MAX_SIZE],
+}
+
+impl Matrix {
+    pub fn identity() -> Matrix {
+        IDENTITY
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn transpose(&self) -> Matrix {
+        let mut transposed = *self;
+        for row in 0..self.size {
+            for column in 0..self.size {
+                transposed.elements[column][row] = self.elements[row][column];
+            }
+        }
+        transposed
+    }
+
+    pub fn submatrix(&self, row: usize, column: usize) -> Matrix {
+        let mut sub = Matrix {
+            size: self.size - 1,
+            elements: [[0.; MAX_SIZE]; MAX_SIZE],
+        };
+        let rows = (0..self.size).filter(|&r| r != row);
+        for (sub_row, r) in rows.enumerate() {
+            let columns = (0..self.size).filter(|&c| c != column);
+            for (sub_column, c) in columns.enumerate() {
+                sub.elements[sub_row][sub_column] = self.elements[r][c];
+            }
+        }
+        sub
+    }
+
+    pub fn minor(&self, row: usize, column: usize) -> f64 {
+        self.submatrix(row, column).determinant()
+    }
+
+    pub fn cofactor(&self, row: usize, column: usize) -> f64 {
+        let minor = self.minor(row, column);
+        if (row + column).is_multiple_of(2) {
+            minor
+        } else {
+            -minor
+        }
+    }
+
+    pub fn determinant(&self) -> f64 {
+        match self.size {
+            1 => self.elements[0][0],
+            2 => {
+                self.elements[0][0] * self.elements[1][1]
+                    - self.elements[0][1] * self.elements[1][0]
+            }
+            _ => (0..self.size)
+                .map(|column| self.elements[0][column] * self.cofactor(0, column))
+                .sum(),
+        }
+    }
+
+    pub fn is_invertible(&self) -> bool {
+        self.determinant() != 0.
+    }
+
+    pub fn inverse(&self) -> Matrix {
+        let det = self.determinant();
+        if det == 0. {
+            panic!("cannot invert a matrix with determinant 0: {:?}", self);
+        }
+        let mut inverted = *self;
+        for row in 0..self.size {
+            for column in 0..self.size {
+                // storing at [column][row] transposes the cofactor matrix
+                inverted.elements[column][row] = self.cofactor(row, column) / det;
+            }
+        }
+        inverted
+    }
+
+    fn affine_elements(&self) -> &[[f64; MAX_SIZE]; MAX_SIZE] {
+        if self.size != MAX_SIZE {
+            panic!(
+                "only a 4x4 matrix can transform points and vectors, not a {}x{}",
+                self.size, self.size
+            );
+        }
+        &self.elements
+    }
+}
+
+impl PartialEq for Matrix {
+    fn eq(&self, other: &Matrix) -> bool {
+        self.size == other.size
+            && (0..self.size).all(|row| {
+                (0..self.size).all(|column| {
+                    abs_diff_eq!(
+                        self.elements[row][column],
+                        other.elements[row][column],
+                        epsilon = EPS
+                    )
+                })
+            })
+    }
+}
+// Should not derive Eq as our f64's could be NaN-y and NaN != NaN
+
+impl Index<(usize, usize)> for Matrix {
+    type Output = f64;
+
+    fn index(&self, (row, column): (usize, usize)) -> &f64 {
+        if row >= self.size || column >= self.size {
+            panic!(
+                "Index ({}, {}) out of range for a {}x{} matrix",
+                row, column, self.size, self.size
+            );
+        }
+        &self.elements[row][column]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Matrix {
+    fn index_mut(&mut self, (row, column): (usize, usize)) -> &mut f64 {
+        if row >= self.size || column >= self.size {
+            panic!(
+                "Index ({}, {}) out of range for a {}x{} matrix",
+                row, column, self.size, self.size
+            );
+        }
+        &mut self.elements[row][column]
+    }
+}
+
+impl Mul<Matrix> for Matrix {
+    type Output = Matrix;
+
+    fn mul(self, other: Matrix) -> Matrix {
+        if self.size != other.size {
+            panic!(
+                "cannot multiply a {}x{} matrix by a {}x{} matrix",
+                self.size, self.size, other.size, other.size
+            );
+        }
+        let mut product = Matrix {
+            size: self.size,
+            elements: [[0.; MAX_SIZE]; MAX_SIZE],
+        };
+        for row in 0..self.size {
+            for column in 0..self.size {
+                product.elements[row][column] = (0..self.size)
+                    .map(|i| self.elements[row][i] * other.elements[i][column])
+                    .sum();
+            }
+        }
+        product
+    }
+}
+
+// `Point` behaves like a tuple with w = 1 so picks up the translation column,
+// `Vector` behaves like w = 0 so is only rotated, scaled and sheared.
+impl Mul<Point> for Matrix {
+    type Output = Point;
+
+    fn mul(self, pointy: Point) -> Point {
+        let m = self.affine_elements();
+        point(
+            m[0][0] * pointy.x + m[0][1] * pointy.y + m[0][2] * pointy.z + m[0][3],
+            m[1][0] * pointy.x + m[1][1] * pointy.y + m[1][2] * pointy.z + m[1][3],
+            m[2][0] * pointy.x + m[2][1] * pointy.y + m[2][2] * pointy.z + m[2][3],
+        )
+    }
+}
+
+impl Mul<Vector> for Matrix {
+    type Output = Vector;
+
+    fn mul(self, vec: Vector) -> Vector {
+        let m = self.affine_elements();
+        vector(
+            m[0][0] * vec.x + m[0][1] * vec.y + m[0][2] * vec.z,
+            m[1][0] * vec.x + m[1][1] * vec.y + m[1][2] * vec.z,
+            m[2][0] * vec.x + m[2][1] * vec.y + m[2][2] * vec.z,
+        )
+    }
+}
+
+pub const IDENTITY: Matrix = Matrix {
+    size: 4,
+    elements: [
+        [1., 0., 0., 0.],
+        [0., 1., 0., 0.],
+        [0., 0., 1., 0.],
+        [0., 0., 0., 1.],
+    ],
+};
+
+pub fn matrix4(rows: [[f64; 4]; 4]) -> Matrix {
+    Matrix {
+        size: 4,
+        elements: rows,
+    }
+}
+
+pub fn matrix3(rows: [[f64; 3]; 3]) -> Matrix {
+    let mut elements = [[0.; MAX_SIZE]; MAX_SIZE];
+    for (row, values) in rows.iter().enumerate() {
+        elements[row][..3].copy_from_slice(values);
+    }
+    Matrix { size: 3, elements }
+}
+
+pub fn matrix2(rows: [[f64; 2]; 2]) -> Matrix {
+    let mut elements = [[0.; MAX_SIZE]; MAX_SIZE];
+    for (row, values) in rows.iter().enumerate() {
+        elements[row][..2].copy_from_slice(values);
+    }
+    Matrix { size: 2, elements }
+}
+
+pub fn identity() -> Matrix {
+    IDENTITY
+}
+
+pub fn transpose(m: Matrix) -> Matrix {
+    m.transpose()
+}
+
+pub fn determinant(m: Matrix) -> f64 {
+    m.determinant()
+}
+
+pub fn inverse(m: Matrix) -> Matrix {
+    m.inverse()
+}
@@ -0,0 +1,112 @@
+use crate::f64_helpers::EPS;
+use crate::intersection::{intersection, intersections, Intersection, Intersections};
+use crate::ray::Ray;
+use crate::shape::{Shape, ShapeBase};
+use crate::tuple::{aabb, point, vector, Aabb, Point, Vector};
+
+#[cfg(test)]
+mod tests_for_plane {
+    use crate::plane::*;
+    use crate::ray::ray;
+    use crate::shape::same_shape;
+    use crate::tuple::point;
+
+    #[test]
+    fn its_normal_is_constant_everywhere() {
+        let p = plane();
+        let expected = vector(0., 1., 0.);
+        assert_eq!(
+            p.local_normal_at(point(0., 0., 0.), &intersection(0., &p)),
+            expected
+        );
+        assert_eq!(
+            p.local_normal_at(point(10., 0., -10.), &intersection(0., &p)),
+            expected
+        );
+        assert_eq!(
+            p.local_normal_at(point(-5., 0., 150.), &intersection(0., &p)),
+            expected
+        );
+    }
+
+    #[test]
+    fn a_ray_parallel_to_it_misses() {
+        let p = plane();
+        let r = ray(point(0., 10., 0.), vector(0., 0., 1.));
+        assert!(p.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn a_coplanar_ray_misses() {
+        let p = plane();
+        let r = ray(point(0., 0., 0.), vector(0., 0., 1.));
+        assert!(p.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn a_ray_intersecting_from_above() {
+        let p = plane();
+        let r = ray(point(0., 1., 0.), vector(0., -1., 0.));
+        let xs = p.local_intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_abs_diff_eq!(xs[0].t, 1.);
+        assert!(same_shape(xs[0].object, &p));
+    }
+
+    #[test]
+    fn a_ray_intersecting_from_below() {
+        let p = plane();
+        let r = ray(point(0., -1., 0.), vector(0., 1., 0.));
+        let xs = p.local_intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_abs_diff_eq!(xs[0].t, 1.);
+    }
+
+    #[test]
+    fn a_grazing_ray_still_intersects() {
+        let p = plane();
+        let r = ray(point(0., 1., 0.), vector(1000., -1., 0.));
+        let xs = p.local_intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_abs_diff_eq!(xs[0].t, 1.);
+    }
+}
+
+/// An infinite plane through the origin, spanning x and z.
+#[derive(Debug, Clone, Default)]
+pub struct Plane {
+    base: ShapeBase,
+}
+
+impl Shape for Plane {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        if local_ray.direction.y.abs() < EPS {
+            return intersections(vec![]);
+        }
+        let t = -local_ray.origin.y / local_ray.direction.y;
+        intersections(vec![intersection(t, self)])
+    }
+
+    fn local_normal_at(&self, _local_point: Point, _hit: &Intersection) -> Vector {
+        vector(0., 1., 0.)
+    }
+
+    fn bounds(&self) -> Aabb {
+        aabb(
+            point(-f64::INFINITY, 0., -f64::INFINITY),
+            point(f64::INFINITY, 0., f64::INFINITY),
+        )
+    }
+}
+
+pub fn plane() -> Plane {
+    Plane::default()
+}
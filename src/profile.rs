@@ -0,0 +1,418 @@
+use crate::f64_helpers::EPS;
+use crate::group::{group, Group};
+use crate::triangle::{smooth_triangle, triangle};
+use crate::tuple::{point, vector, Point, Vector};
+use std::f64::consts::PI;
+
+#[cfg(test)]
+mod tests_for_profile {
+    use crate::f64_helpers::EPS;
+    use crate::profile::*;
+    use crate::ray::ray;
+    use crate::shape::Shape;
+
+    fn unit_square() -> Profile {
+        polyline_profile(vec![(0., 0.), (1., 0.), (1., 1.), (0., 1.)])
+    }
+
+    fn first_hit_normal(g: &Group, origin: Point, direction: Vector) -> (f64, Vector) {
+        let r = ray(origin, direction);
+        let xs = g.intersect(&r);
+        let hit = xs.hit().unwrap();
+        (hit.t, hit.object.normal_at(r.position(hit.t), hit))
+    }
+
+    #[test]
+    fn a_bezier_profile_is_sampled_along_each_curve() {
+        let p = bezier_profile(&[(0., 0.), (1., 0.), (1., 1.), (0., 1.)], 4);
+        assert_eq!(p.points.len(), 5);
+        assert!(p.smooth);
+        assert_eq!(p.points[0], (0., 0.));
+        assert_abs_diff_eq!(p.points[2].0, 0.75);
+        assert_abs_diff_eq!(p.points[2].1, 0.5);
+        assert_eq!(p.points[4], (0., 1.));
+    }
+
+    #[test]
+    fn bezier_curves_can_be_chained() {
+        let p = bezier_profile(
+            &[
+                (0., 0.),
+                (1., 0.),
+                (1., 1.),
+                (0., 1.),
+                (-1., 1.),
+                (-1., 2.),
+                (0., 2.),
+            ],
+            2,
+        );
+        assert_eq!(p.points.len(), 5);
+        assert_eq!(p.points[2], (0., 1.));
+    }
+
+    #[test]
+    #[should_panic]
+    fn a_bezier_profile_needs_whole_curves() {
+        bezier_profile(&[(0., 0.), (1., 0.), (1., 1.)], 4);
+    }
+
+    #[test]
+    fn a_lathed_line_makes_a_tube() {
+        let tube = lathe(&polyline_profile(vec![(1., 0.), (1., 1.)]), 64);
+        assert_eq!(tube.children().len(), 128);
+        let (t, n) = first_hit_normal(&tube, point(0., 0.5, -5.), vector(0., 0., 1.));
+        assert_abs_diff_eq!(t, 4., epsilon = 0.01);
+        assert_eq!(n, vector(0., 0., -1.));
+        let r = ray(point(0., 0.5, -5.), vector(0., 0., 1.));
+        assert_eq!(tube.intersect(&r).len(), 2);
+    }
+
+    #[test]
+    fn lathing_leaves_out_triangles_squashed_onto_the_axis() {
+        let cup = lathe(&polyline_profile(vec![(0., 0.), (1., 0.), (1., 1.)]), 8);
+        assert_eq!(cup.children().len(), 8 + 16);
+        let (t, n) = first_hit_normal(&cup, point(0., 5., 0.), vector(0., -1., 0.));
+        assert_abs_diff_eq!(t, 5., epsilon = EPS);
+        assert_eq!(n, vector(0., -1., 0.));
+    }
+
+    #[test]
+    fn a_polyline_profile_keeps_its_corners_sharp() {
+        let profile = polyline_profile(vec![(1., 0.), (1., 1.), (0., 2.)]);
+        let crisp = lathe(&profile, 64);
+        let (_, n) = first_hit_normal(&crisp, point(0., 0.99, -5.), vector(0., 0., 1.));
+        assert_eq!(n, vector(0., 0., -1.));
+
+        let rounded = lathe(
+            &Profile {
+                smooth: true,
+                ..profile
+            },
+            64,
+        );
+        let (_, n) = first_hit_normal(&rounded, point(0., 0.99, -5.), vector(0., 0., 1.));
+        assert!(n.y > 0.3);
+    }
+
+    #[test]
+    fn a_swept_square_makes_a_box() {
+        let b = sweep(&unit_square(), 2.);
+        assert_eq!(b.children().len(), 4 * 2 + 2 * 2);
+        let r = ray(point(0.2, 0.6, -5.), vector(0., 0., 1.));
+        let ts: Vec<f64> = b.intersect(&r).iter().map(|i| i.t).collect();
+        assert_eq!(ts, vec![5., 7.]);
+        let (t, n) = first_hit_normal(&b, point(-5., 0.5, 1.), vector(1., 0., 0.));
+        assert_abs_diff_eq!(t, 5.);
+        assert_eq!(n, vector(-1., 0., 0.));
+    }
+
+    #[test]
+    fn the_caps_of_a_sweep_face_outwards() {
+        let b = sweep(&unit_square(), 2.);
+        let (t, n) = first_hit_normal(&b, point(0.2, 0.6, -5.), vector(0., 0., 1.));
+        assert_abs_diff_eq!(t, 5.);
+        assert_eq!(n, vector(0., 0., -1.));
+        let (t, n) = first_hit_normal(&b, point(0.2, 0.6, 10.), vector(0., 0., -1.));
+        assert_abs_diff_eq!(t, 8.);
+        assert_eq!(n, vector(0., 0., 1.));
+    }
+
+    #[test]
+    fn a_clockwise_profile_still_sweeps_with_outward_normals() {
+        let mut points = unit_square().points;
+        points.reverse();
+        let b = sweep(&polyline_profile(points), 2.);
+        let (_, n) = first_hit_normal(&b, point(0.5, -5., 1.), vector(0., 1., 0.));
+        assert_eq!(n, vector(0., -1., 0.));
+    }
+
+    #[test]
+    fn a_concave_outline_is_split_into_triangles_covering_it() {
+        // an L made of three unit squares
+        let l = vec![(0., 0.), (2., 0.), (2., 1.), (1., 1.), (1., 2.), (0., 2.)];
+        let triangles = triangulate(&l);
+        assert_eq!(triangles.len(), l.len() - 2);
+        let area: f64 = triangles
+            .iter()
+            .map(|&(a, b, c)| turn(l[a], l[b], l[c]) / 2.)
+            .sum();
+        assert_abs_diff_eq!(area, 3., epsilon = EPS);
+        assert!(triangles
+            .iter()
+            .all(|&(a, b, c)| turn(l[a], l[b], l[c]) > 0.));
+    }
+
+    #[test]
+    fn the_caps_of_a_concave_sweep_leave_the_notch_open() {
+        let l = polyline_profile(vec![
+            (0., 0.),
+            (2., 0.),
+            (2., 1.),
+            (1., 1.),
+            (1., 2.),
+            (0., 2.),
+        ]);
+        let s = sweep(&l, 1.);
+        let r = ray(point(1.5, 1.5, -5.), vector(0., 0., 1.));
+        assert!(s.intersect(&r).is_empty());
+        let r = ray(point(0.3, 1.45, -5.), vector(0., 0., 1.));
+        assert_eq!(s.intersect(&r).len(), 2);
+    }
+}
+
+/// A 2D curve in the xy plane, used to build shapes by spinning it around
+/// the y axis (`lathe`) or pushing it along the z axis (`sweep`).
+///
+/// A smooth profile blends normals across the joins between its points,
+/// while a non-smooth one keeps a crease at each of them.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub points: Vec<(f64, f64)>,
+    pub smooth: bool,
+}
+
+impl Profile {
+    /// The 2D normal at the start and end of each segment. Normals point to
+    /// the right of the direction of travel, which is outwards for a
+    /// counter-clockwise outline or a lathe profile drawn upwards.
+    fn segment_normals(&self, closed: bool) -> Vec<((f64, f64), (f64, f64))> {
+        let n = self.points.len();
+        let segments = if closed { n } else { n - 1 };
+        let flat: Vec<(f64, f64)> = (0..segments)
+            .map(|i| {
+                let (ax, ay) = self.points[i];
+                let (bx, by) = self.points[(i + 1) % n];
+                normalize_2d((by - ay, ax - bx))
+            })
+            .collect();
+        if !self.smooth {
+            return flat.iter().map(|&normal| (normal, normal)).collect();
+        }
+        // average with the neighbouring segment at each shared point
+        let blend = |a: (f64, f64), b: (f64, f64)| normalize_2d((a.0 + b.0, a.1 + b.1));
+        (0..segments)
+            .map(|i| {
+                let previous = if i > 0 || closed {
+                    Some(flat[(i + segments - 1) % segments])
+                } else {
+                    None
+                };
+                let next = if i + 1 < segments || closed {
+                    Some(flat[(i + 1) % segments])
+                } else {
+                    None
+                };
+                let start = previous.map_or(flat[i], |p| blend(p, flat[i]));
+                let end = next.map_or(flat[i], |n| blend(flat[i], n));
+                (start, end)
+            })
+            .collect()
+    }
+}
+
+fn normalize_2d((x, y): (f64, f64)) -> (f64, f64) {
+    let length = (x * x + y * y).sqrt();
+    (x / length, y / length)
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `c`: positive when it
+/// turns counter-clockwise.
+fn turn(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+/// Splits a simple counter-clockwise polygon into triangles by clipping off
+/// "ears": corners that turn left and have no other vertex inside them.
+fn triangulate(points: &[(f64, f64)]) -> Vec<(usize, usize, usize)> {
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = vec![];
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let corner = |i: usize| {
+            (
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            )
+        };
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = corner(i);
+            let (pa, pb, pc) = (points[a], points[b], points[c]);
+            turn(pa, pb, pc) > 0.
+                && !remaining.iter().any(|&other| {
+                    let p = points[other];
+                    other != a
+                        && other != b
+                        && other != c
+                        && turn(pa, pb, p) >= 0.
+                        && turn(pb, pc, p) >= 0.
+                        && turn(pc, pa, p) >= 0.
+                })
+        });
+        let ear = match ear {
+            Some(ear) => ear,
+            None => panic!("profile outline crosses itself and cannot be capped"),
+        };
+        triangles.push(corner(ear));
+        remaining.remove(ear);
+    }
+    triangles.push((remaining[0], remaining[1], remaining[2]));
+    triangles
+}
+
+/// Triangles whose corners have collapsed together, e.g. where a lathe
+/// profile touches the axis, can never be hit and have no normal.
+fn is_degenerate(p1: Point, p2: Point, p3: Point) -> bool {
+    (p2 - p1).cross(p3 - p1).magnitude() < EPS * EPS
+}
+
+/// Adds the two triangles of the quad `a`, `b`, `c`, `d` with a normal
+/// at each corner.
+fn add_quad(g: &mut Group, corners: [(Point, Vector); 4]) {
+    let [a, b, c, d] = corners;
+    for &(p1, p2, p3) in [(a, b, c), (a, c, d)].iter() {
+        if !is_degenerate(p1.0, p2.0, p3.0) {
+            g.add_child(Box::new(smooth_triangle(
+                p1.0, p2.0, p3.0, p1.1, p2.1, p3.1,
+            )));
+        }
+    }
+}
+
+/// A profile through the given points, with sharp corners between them.
+pub fn polyline_profile(points: Vec<(f64, f64)>) -> Profile {
+    Profile {
+        points,
+        smooth: false,
+    }
+}
+
+/// A smooth profile sampled from a chain of cubic Bezier curves.
+///
+/// Each curve shares its first control point with the end of the one
+/// before, so `controls` holds `3n + 1` points for `n` curves, and each
+/// curve is sampled at `samples` evenly spaced steps.
+pub fn bezier_profile(controls: &[(f64, f64)], samples: usize) -> Profile {
+    if controls.len() < 4 || !(controls.len() - 1).is_multiple_of(3) {
+        panic!(
+            "cubic Bezier curves need 3n + 1 control points, found {}",
+            controls.len()
+        );
+    }
+    let mut points = vec![controls[0]];
+    for first in (0..controls.len() - 1).step_by(3) {
+        let (p0, p1, p2, p3) = (
+            controls[first],
+            controls[first + 1],
+            controls[first + 2],
+            controls[first + 3],
+        );
+        for step in 1..=samples {
+            let t = step as f64 / samples as f64;
+            let s = 1. - t;
+            let weights = [s * s * s, 3. * s * s * t, 3. * s * t * t, t * t * t];
+            let blend = |axis: fn((f64, f64)) -> f64| {
+                weights[0] * axis(p0)
+                    + weights[1] * axis(p1)
+                    + weights[2] * axis(p2)
+                    + weights[3] * axis(p3)
+            };
+            points.push((blend(|p| p.0), blend(|p| p.1)));
+        }
+    }
+    Profile {
+        points,
+        smooth: true,
+    }
+}
+
+/// Spins a profile of `(radius, y)` points around the y axis in `segments`
+/// steps, building a mesh of smooth triangles.
+///
+/// Normals are always blended around the axis; whether they are blended
+/// along the profile is up to `profile.smooth`. Put the first or last
+/// point on the axis to close off the end.
+pub fn lathe(profile: &Profile, segments: usize) -> Group {
+    if profile.points.len() < 2 || segments < 3 {
+        panic!("a lathe needs at least 2 profile points and 3 segments");
+    }
+    let normals = profile.segment_normals(false);
+    let angle = |k: usize| 2. * PI * (k % segments) as f64 / segments as f64;
+    let spin = |(r, y): (f64, f64), k: usize| point(r * angle(k).cos(), y, r * angle(k).sin());
+    let spin_normal =
+        |(nr, ny): (f64, f64), k: usize| vector(nr * angle(k).cos(), ny, nr * angle(k).sin());
+    let mut g = group();
+    for (i, window) in profile.points.windows(2).enumerate() {
+        let (start, end) = normals[i];
+        for k in 0..segments {
+            add_quad(
+                &mut g,
+                [
+                    (spin(window[0], k), spin_normal(start, k)),
+                    (spin(window[1], k), spin_normal(end, k)),
+                    (spin(window[1], k + 1), spin_normal(end, k + 1)),
+                    (spin(window[0], k + 1), spin_normal(start, k + 1)),
+                ],
+            );
+        }
+    }
+    g
+}
+
+/// Pushes a closed outline in the xy plane from z = 0 to z = `depth`,
+/// building a prism with flat caps at each end.
+///
+/// The outline may be concave, as mouldings often are, but must not cross
+/// itself. Either winding works.
+pub fn sweep(profile: &Profile, depth: f64) -> Group {
+    let mut outline = profile.clone();
+    if outline.points.len() > 1 && outline.points.first() == outline.points.last() {
+        outline.points.pop();
+    }
+    if outline.points.len() < 3 {
+        panic!("a sweep needs an outline of at least 3 points");
+    }
+    let n = outline.points.len();
+    let area: f64 = (0..n)
+        .map(|i| {
+            let (a, b) = (outline.points[i], outline.points[(i + 1) % n]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum();
+    if area < 0. {
+        outline.points.reverse();
+    }
+    let points = &outline.points;
+    let at = |(x, y): (f64, f64), z: f64| point(x, y, z);
+    let along = |(nx, ny): (f64, f64)| vector(nx, ny, 0.);
+
+    let mut g = group();
+    for (i, &(start, end)) in outline.segment_normals(true).iter().enumerate() {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        add_quad(
+            &mut g,
+            [
+                (at(a, 0.), along(start)),
+                (at(b, 0.), along(end)),
+                (at(b, depth), along(end)),
+                (at(a, depth), along(start)),
+            ],
+        );
+    }
+    // triangle() takes its normal from e2 x e1, so a counter-clockwise
+    // triangle faces -z: right for the near cap, reversed for the far one
+    for (a, b, c) in triangulate(points) {
+        g.add_child(Box::new(triangle(
+            at(points[a], 0.),
+            at(points[b], 0.),
+            at(points[c], 0.),
+        )));
+        g.add_child(Box::new(triangle(
+            at(points[a], depth),
+            at(points[c], depth),
+            at(points[b], depth),
+        )));
+    }
+    g
+}
@@ -0,0 +1,195 @@
+use crate::f64_helpers::EPS;
+use crate::matrix::{matrix4, Matrix};
+use std::cmp::PartialEq;
+
+#[cfg(test)]
+mod tests_for_quaternion {
+    use crate::matrix::IDENTITY;
+    use crate::quaternion::*;
+    use crate::transform::{rotation_x, rotation_y, rotation_z};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn the_identity_rotation_is_the_unit_quaternion() {
+        assert_eq!(rotation_quaternion(&IDENTITY), quaternion(1., 0., 0., 0.));
+        assert_eq!(quaternion(1., 0., 0., 0.).to_matrix(), IDENTITY);
+    }
+
+    #[test]
+    fn it_round_trips_through_rotation_matrices() {
+        let rotations = [
+            rotation_x(PI / 3.),
+            rotation_y(-PI / 2.),
+            rotation_z(PI),
+            rotation_x(0.4) * rotation_y(2.9) * rotation_z(-1.3),
+        ];
+        for m in rotations.iter() {
+            assert_eq!(rotation_quaternion(m).to_matrix(), *m);
+        }
+    }
+
+    #[test]
+    fn it_has_unit_length() {
+        let q = rotation_quaternion(&(rotation_x(0.4) * rotation_z(-1.3)));
+        assert_abs_diff_eq!(q.dot(q), 1., epsilon = EPS);
+    }
+
+    #[test]
+    fn slerp_runs_evenly_between_rotations() {
+        let start = rotation_quaternion(&IDENTITY);
+        let end = rotation_quaternion(&rotation_z(PI / 2.));
+        assert_eq!(start.slerp(end, 0.), start);
+        assert_eq!(start.slerp(end, 1.), end);
+        assert_eq!(start.slerp(end, 0.5).to_matrix(), rotation_z(PI / 4.));
+        assert_eq!(start.slerp(end, 0.25).to_matrix(), rotation_z(PI / 8.));
+    }
+
+    #[test]
+    fn slerp_takes_the_short_way_round() {
+        let start = rotation_quaternion(&rotation_y(0.1));
+        // the same rotation as rotation_y(-0.1), but written the long way
+        let end = rotation_quaternion(&rotation_y(-0.1)).negated();
+        assert_eq!(start.slerp(end, 0.5).to_matrix(), IDENTITY);
+    }
+}
+
+/// A rotation as a unit quaternion `w + xi + yj + zk`, which unlike a
+/// matrix can be smoothly interpolated from one orientation to another.
+#[derive(Debug, Copy, Clone)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    pub fn dot(self, other: Quaternion) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    /// The same rotation: `q` and `-q` both describe it.
+    pub fn negated(self) -> Quaternion {
+        quaternion(-self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn normalize(self) -> Quaternion {
+        let length = self.dot(self).sqrt();
+        quaternion(
+            self.w / length,
+            self.x / length,
+            self.y / length,
+            self.z / length,
+        )
+    }
+
+    pub fn to_matrix(self) -> Matrix {
+        let Quaternion { w, x, y, z } = self;
+        matrix4([
+            [
+                1. - 2. * (y * y + z * z),
+                2. * (x * y - w * z),
+                2. * (x * z + w * y),
+                0.,
+            ],
+            [
+                2. * (x * y + w * z),
+                1. - 2. * (x * x + z * z),
+                2. * (y * z - w * x),
+                0.,
+            ],
+            [
+                2. * (x * z - w * y),
+                2. * (y * z + w * x),
+                1. - 2. * (x * x + y * y),
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Spherical linear interpolation: turns at a constant rate from `self`
+    /// at `t = 0` to `other` at `t = 1`, the shorter way round.
+    pub fn slerp(self, other: Quaternion, t: f64) -> Quaternion {
+        let (other, cos_angle) = if self.dot(other) < 0. {
+            (other.negated(), -self.dot(other))
+        } else {
+            (other, self.dot(other))
+        };
+        let lerp = |a: f64, b: f64| a * (1. - t) + b * t;
+        if cos_angle > 1. - EPS {
+            // too close to divide by the sine: a straight line will do
+            return quaternion(
+                lerp(self.w, other.w),
+                lerp(self.x, other.x),
+                lerp(self.y, other.y),
+                lerp(self.z, other.z),
+            )
+            .normalize();
+        }
+        let angle = cos_angle.acos();
+        let a = ((1. - t) * angle).sin() / angle.sin();
+        let b = (t * angle).sin() / angle.sin();
+        quaternion(
+            a * self.w + b * other.w,
+            a * self.x + b * other.x,
+            a * self.y + b * other.y,
+            a * self.z + b * other.z,
+        )
+    }
+}
+
+impl PartialEq for Quaternion {
+    fn eq(&self, other: &Quaternion) -> bool {
+        abs_diff_eq!(self.w, other.w, epsilon = EPS)
+            && abs_diff_eq!(self.x, other.x, epsilon = EPS)
+            && abs_diff_eq!(self.y, other.y, epsilon = EPS)
+            && abs_diff_eq!(self.z, other.z, epsilon = EPS)
+    }
+}
+// Should not derive Eq as our f64's could be NaN-y and NaN != NaN
+
+pub fn quaternion(w: f64, x: f64, y: f64, z: f64) -> Quaternion {
+    Quaternion { w, x, y, z }
+}
+
+/// The quaternion for the rotation in the upper 3x3 of `m`, which must be a
+/// pure rotation. Works from whichever of `w`, `x`, `y` or `z` is largest
+/// to avoid dividing by something near zero.
+pub fn rotation_quaternion(m: &Matrix) -> Quaternion {
+    let trace = m[(0, 0)] + m[(1, 1)] + m[(2, 2)];
+    let q = if trace > 0. {
+        let s = 2. * (trace + 1.).sqrt();
+        quaternion(
+            s / 4.,
+            (m[(2, 1)] - m[(1, 2)]) / s,
+            (m[(0, 2)] - m[(2, 0)]) / s,
+            (m[(1, 0)] - m[(0, 1)]) / s,
+        )
+    } else if m[(0, 0)] > m[(1, 1)] && m[(0, 0)] > m[(2, 2)] {
+        let s = 2. * (1. + m[(0, 0)] - m[(1, 1)] - m[(2, 2)]).sqrt();
+        quaternion(
+            (m[(2, 1)] - m[(1, 2)]) / s,
+            s / 4.,
+            (m[(0, 1)] + m[(1, 0)]) / s,
+            (m[(0, 2)] + m[(2, 0)]) / s,
+        )
+    } else if m[(1, 1)] > m[(2, 2)] {
+        let s = 2. * (1. + m[(1, 1)] - m[(0, 0)] - m[(2, 2)]).sqrt();
+        quaternion(
+            (m[(0, 2)] - m[(2, 0)]) / s,
+            (m[(0, 1)] + m[(1, 0)]) / s,
+            s / 4.,
+            (m[(1, 2)] + m[(2, 1)]) / s,
+        )
+    } else {
+        let s = 2. * (1. + m[(2, 2)] - m[(0, 0)] - m[(1, 1)]).sqrt();
+        quaternion(
+            (m[(1, 0)] - m[(0, 1)]) / s,
+            (m[(0, 2)] + m[(2, 0)]) / s,
+            (m[(1, 2)] + m[(2, 1)]) / s,
+            s / 4.,
+        )
+    };
+    q.normalize()
+}
@@ -0,0 +1,107 @@
+use crate::matrix::Matrix;
+use crate::tuple::{Point, Vector};
+
+#[cfg(test)]
+mod tests_for_ray {
+    use crate::ray::*;
+    use crate::transform::{scaling, translation};
+    use crate::tuple::{point, vector};
+
+    #[test]
+    fn it_has_an_origin_and_direction() {
+        let origin = point(1., 2., 3.);
+        let direction = vector(4., 5., 6.);
+        let r = ray(origin, direction);
+        assert_eq!(r.origin, origin);
+        assert_eq!(r.direction, direction);
+    }
+
+    #[test]
+    fn it_is_sent_as_the_shutter_opens_unless_told_otherwise() {
+        let r = ray(point(1., 2., 3.), vector(4., 5., 6.));
+        assert_abs_diff_eq!(r.time, 0.);
+        let r = ray_at_time(point(1., 2., 3.), vector(4., 5., 6.), 0.25);
+        assert_abs_diff_eq!(r.time, 0.25);
+    }
+
+    #[test]
+    fn it_can_compute_a_point_from_a_distance() {
+        let r = ray(point(2., 3., 4.), vector(1., 0., 0.));
+        assert_eq!(r.position(0.), point(2., 3., 4.));
+        assert_eq!(r.position(1.), point(3., 3., 4.));
+        assert_eq!(r.position(-1.), point(1., 3., 4.));
+        assert_eq!(r.position(2.5), point(4.5, 3., 4.));
+        assert_eq!(position(r, 2.5), point(4.5, 3., 4.));
+    }
+
+    #[test]
+    fn it_can_be_translated() {
+        let r = ray(point(1., 2., 3.), vector(0., 1., 0.));
+        let r2 = r.transform(&translation(3., 4., 5.));
+        assert_eq!(r2.origin, point(4., 6., 8.));
+        assert_eq!(r2.direction, vector(0., 1., 0.));
+    }
+
+    #[test]
+    fn it_can_be_scaled() {
+        let r = ray(point(1., 2., 3.), vector(0., 1., 0.));
+        let r2 = r.transform(&scaling(2., 3., 4.));
+        assert_eq!(r2.origin, point(2., 6., 12.));
+        assert_eq!(r2.direction, vector(0., 3., 0.));
+    }
+
+    #[test]
+    fn transforming_keeps_its_time() {
+        let r = ray_at_time(point(1., 2., 3.), vector(0., 1., 0.), 0.5);
+        assert_abs_diff_eq!(r.transform(&scaling(2., 3., 4.)).time, 0.5);
+    }
+
+    #[test]
+    fn transforming_returns_a_new_ray() {
+        let r = ray(point(1., 2., 3.), vector(0., 1., 0.));
+        let _ = transform(r, &scaling(2., 3., 4.));
+        assert_eq!(r.origin, point(1., 2., 3.));
+        assert_eq!(r.direction, vector(0., 1., 0.));
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray {
+    pub origin: Point,
+    pub direction: Vector,
+    // When the ray was sent, from 0 as the shutter opens to just before 1
+    // as it closes; moving shapes are intersected where they were then.
+    pub time: f64,
+}
+
+impl Ray {
+    pub fn position(&self, t: f64) -> Point {
+        self.origin + self.direction * t
+    }
+
+    // Direction is deliberately left un-normalized so that `t` values
+    // found in object space are still valid in world space.
+    pub fn transform(&self, m: &Matrix) -> Ray {
+        ray_at_time(*m * self.origin, *m * self.direction, self.time)
+    }
+}
+
+pub fn ray(origin: Point, direction: Vector) -> Ray {
+    ray_at_time(origin, direction, 0.)
+}
+
+pub fn ray_at_time(origin: Point, direction: Vector, time: f64) -> Ray {
+    Ray {
+        origin,
+        direction,
+        time,
+    }
+}
+
+pub fn position(r: Ray, t: f64) -> Point {
+    r.position(t)
+}
+
+pub fn transform(r: Ray, m: &Matrix) -> Ray {
+    r.transform(m)
+}
@@ -0,0 +1,487 @@
+use crate::intersection::{intersection, intersections, Intersection, Intersections};
+use crate::ray::{ray, Ray};
+use crate::shape::{Shape, ShapeBase};
+use crate::transform::translation;
+use crate::tuple::{aabb, point, vector, Aabb, Point, Vector};
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(test)]
+mod tests_for_sdf {
+    use crate::color::WHITE;
+    use crate::csg::{csg, CsgOperation};
+    use crate::cube::cube;
+    use crate::f64_helpers::EPS;
+    use crate::intersection::intersection;
+    use crate::light::point_light;
+    use crate::sdf::*;
+    use crate::shape::same_shape;
+    use crate::sphere::sphere;
+    use crate::transform::{scaling, translation};
+    use crate::world::world;
+
+    #[test]
+    fn a_sphere_distance_is_signed() {
+        let s = sdf_sphere(1.);
+        assert_abs_diff_eq!(s.distance(point(3., 0., 0.)), 2.);
+        assert_abs_diff_eq!(s.distance(point(0., 1., 0.)), 0.);
+        assert_abs_diff_eq!(s.distance(point(0., 0., 0.)), -1.);
+    }
+
+    #[test]
+    fn a_box_distance_measures_to_the_nearest_face_edge_or_corner() {
+        let b = sdf_box(vector(1., 2., 3.));
+        assert_abs_diff_eq!(b.distance(point(3., 0., 0.)), 2.);
+        assert_abs_diff_eq!(b.distance(point(0., 0., 0.)), -1.);
+        assert_abs_diff_eq!(b.distance(point(2., 3., 3.)), f64::sqrt(2.), epsilon = EPS);
+    }
+
+    #[test]
+    fn a_round_box_is_grown_by_its_radius() {
+        let b = sdf_round_box(vector(1., 1., 1.), 0.5);
+        assert_abs_diff_eq!(b.distance(point(3., 0., 0.)), 1.5);
+    }
+
+    #[test]
+    fn a_union_takes_the_nearer_surface() {
+        let u = sdf_sphere(1.)
+            .translate(vector(-2., 0., 0.))
+            .union(sdf_sphere(1.).translate(vector(2., 0., 0.)));
+        assert_abs_diff_eq!(u.distance(point(0., 0., 0.)), 1.);
+        assert_abs_diff_eq!(u.distance(point(4., 0., 0.)), 1.);
+    }
+
+    #[test]
+    fn a_smooth_union_blends_where_shapes_are_close() {
+        let a = sdf_sphere(1.).translate(vector(-1.2, 0., 0.));
+        let b = sdf_sphere(1.).translate(vector(1.2, 0., 0.));
+        let hard = a.clone().union(b.clone());
+        let smooth = a.smooth_union(b, 0.5);
+        let between = point(0., 0., 0.);
+        assert!(smooth.distance(between) < hard.distance(between));
+        // far from the seam the blend makes no difference
+        assert_abs_diff_eq!(
+            smooth.distance(point(5., 0., 0.)),
+            hard.distance(point(5., 0., 0.)),
+            epsilon = EPS
+        );
+    }
+
+    #[test]
+    fn repetition_tiles_space() {
+        let r = sdf_sphere(0.5).repeat(vector(3., 3., 3.));
+        assert_abs_diff_eq!(r.distance(point(0., 0., 0.)), -0.5);
+        assert_abs_diff_eq!(r.distance(point(3., 0., -6.)), -0.5, epsilon = EPS);
+        assert_abs_diff_eq!(r.distance(point(1.5, 0., 0.)), 1., epsilon = EPS);
+    }
+
+    #[test]
+    fn a_closure_can_be_used_as_a_distance_function() {
+        let plane = sdf_function(|p| p.y);
+        assert_abs_diff_eq!(plane.distance(point(4., 2., 1.)), 2.);
+    }
+
+    #[test]
+    fn its_bounds_follow_the_tree() {
+        let u = sdf_sphere(1.)
+            .translate(vector(-2., 0., 0.))
+            .union(sdf_box(vector(1., 2., 3.)));
+        assert_eq!(u.bounds().min, point(-3., -2., -3.));
+        assert_eq!(u.bounds().max, point(1., 2., 3.));
+        assert_eq!(
+            sdf_sphere(1.).repeat(vector(3., 3., 3.)).bounds().max.x,
+            f64::INFINITY
+        );
+    }
+
+    #[test]
+    fn sphere_tracing_finds_the_surface() {
+        let s = sdf_shape(sdf_sphere(1.));
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let xs = s.local_intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_abs_diff_eq!(xs[0].t, 4., epsilon = 0.001);
+        assert_abs_diff_eq!(xs[1].t, 6., epsilon = 0.001);
+        assert!(same_shape(xs[0].object, &s));
+    }
+
+    #[test]
+    fn sphere_tracing_finds_the_surface_behind_the_ray_from_inside() {
+        let s = sdf_shape(sdf_sphere(1.));
+        let r = ray(point(0., 0., 0.), vector(0., 0., 1.));
+        let ts: Vec<f64> = s.local_intersect(&r).iter().map(|i| i.t).collect();
+        assert_eq!(ts.len(), 2);
+        assert_abs_diff_eq!(ts[0], -1., epsilon = 0.001);
+        assert_abs_diff_eq!(ts[1], 1., epsilon = 0.001);
+    }
+
+    #[test]
+    fn a_chord_near_the_rim_is_entered_and_left() {
+        let s = sdf_shape(sdf_sphere(1.));
+        for y in [0.99774, 0.998, 0.999] {
+            let r = ray(point(0., y, -5.), vector(0., 0., 1.));
+            let xs = s.local_intersect(&r);
+            let half_chord = f64::sqrt(1. - y * y);
+            assert_eq!(xs.len(), 2);
+            assert_abs_diff_eq!(xs[0].t, 5. - half_chord, epsilon = 0.001);
+            assert_abs_diff_eq!(xs[1].t, 5. + half_chord, epsilon = 0.001);
+        }
+    }
+
+    #[test]
+    fn an_unbounded_sdf_ignores_the_surface_the_ray_starts_on() {
+        let s = sdf_shape(sdf_function(|p| p.y));
+        let r = ray(point(0., 0.000001, 0.), vector(0., 1., 0.));
+        assert!(s.local_intersect(&r).is_empty());
+        let r = ray(point(0., 2., 0.), vector(0., -1., 0.));
+        assert_abs_diff_eq!(s.local_intersect(&r)[0].t, 2., epsilon = 0.001);
+    }
+
+    #[test]
+    fn a_scaled_sdf_shape_does_not_shadow_itself() {
+        let mut analytic = world();
+        let mut s = sphere();
+        s.set_transform(scaling(10., 10., 10.));
+        analytic.objects.push(Box::new(s));
+        analytic
+            .lights
+            .push(point_light(point(-100., 100., -100.), WHITE));
+        let mut traced = world();
+        let mut s = sdf_shape(sdf_sphere(1.));
+        s.set_transform(scaling(10., 10., 10.));
+        traced.objects.push(Box::new(s));
+        traced.lights = analytic.lights.clone();
+        for target in [point(0., 0., 0.), point(-3., 4., 0.), point(2., 1., 0.)] {
+            let from = point(0., 0., -50.);
+            let r = ray(from, (target - from).normalize());
+            let expected = analytic.color_at(&r);
+            let actual = traced.color_at(&r);
+            assert!(expected.red > 0.5);
+            assert_abs_diff_eq!(actual.red, expected.red, epsilon = 0.001);
+            assert_abs_diff_eq!(actual.green, expected.green, epsilon = 0.001);
+            assert_abs_diff_eq!(actual.blue, expected.blue, epsilon = 0.001);
+        }
+    }
+
+    #[test]
+    fn an_sdf_shape_can_be_combined_with_csg() {
+        let mut c = cube();
+        c.set_transform(translation(0., 0., 1.5));
+        let d = csg(
+            CsgOperation::Difference,
+            Box::new(sdf_shape(sdf_sphere(1.))),
+            Box::new(c),
+        );
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let ts: Vec<f64> = d.local_intersect(&r).iter().map(|i| i.t).collect();
+        assert_eq!(ts.len(), 2);
+        assert_abs_diff_eq!(ts[0], 4., epsilon = 0.001);
+        assert_abs_diff_eq!(ts[1], 5.5, epsilon = 0.001);
+    }
+
+    #[test]
+    fn sphere_tracing_keeps_t_for_unnormalized_directions() {
+        let s = sdf_shape(sdf_sphere(1.));
+        let r = ray(point(0., 0., -5.), vector(0., 0., 2.));
+        let xs = s.local_intersect(&r);
+        assert_abs_diff_eq!(xs[0].t, 2., epsilon = 0.001);
+    }
+
+    #[test]
+    fn sphere_tracing_gives_up_on_a_miss() {
+        let s = sdf_shape(sdf_sphere(1.));
+        let r = ray(point(0., 2., -5.), vector(0., 0., 1.));
+        assert!(s.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn a_transformed_sdf_shape_is_traced_in_object_space() {
+        let mut s = sdf_shape(sdf_sphere(1.));
+        s.set_transform(scaling(2., 2., 2.));
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let xs = s.intersect(&r);
+        assert_abs_diff_eq!(xs[0].t, 3., epsilon = 0.001);
+    }
+
+    #[test]
+    fn its_normal_is_the_gradient_of_the_distance() {
+        let s = sdf_shape(sdf_sphere(1.));
+        let third = f64::sqrt(3.) / 3.;
+        let p = point(third, third, third);
+        let n = s.normal_at(p, &intersection(0., &s));
+        assert_abs_diff_eq!(n.x, third, epsilon = 0.0001);
+        assert_abs_diff_eq!(n.y, third, epsilon = 0.0001);
+        assert_abs_diff_eq!(n.z, third, epsilon = 0.0001);
+        let b = sdf_shape(sdf_box(vector(1., 1., 1.)));
+        assert_eq!(
+            b.normal_at(point(1., 0.3, 0.2), &intersection(0., &b)),
+            vector(1., 0., 0.)
+        );
+    }
+}
+
+/// A signed distance function: negative inside the surface, positive
+/// outside, and never more than the true distance to the surface.
+///
+/// Primitives are centred on the origin and combined into a tree with the
+/// builder methods, e.g. `sdf_sphere(1.).smooth_union(sdf_box(..), 0.2)`.
+#[derive(Clone)]
+pub enum Sdf {
+    Sphere { radius: f64 },
+    Box { half_extents: Vector },
+    RoundBox { half_extents: Vector, radius: f64 },
+    Translate { offset: Vector, inner: Box<Sdf> },
+    Union(Box<Sdf>, Box<Sdf>),
+    SmoothUnion { a: Box<Sdf>, b: Box<Sdf>, k: f64 },
+    Repeat { period: Vector, inner: Box<Sdf> },
+    Function(Arc<dyn Fn(Point) -> f64 + Send + Sync>),
+}
+
+impl Sdf {
+    pub fn distance(&self, p: Point) -> f64 {
+        match self {
+            Sdf::Sphere { radius } => (p - point(0., 0., 0.)).magnitude() - radius,
+            Sdf::Box { half_extents } => box_distance(p, *half_extents),
+            Sdf::RoundBox {
+                half_extents,
+                radius,
+            } => box_distance(p, *half_extents) - radius,
+            Sdf::Translate { offset, inner } => inner.distance(p - *offset),
+            Sdf::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Sdf::SmoothUnion { a, b, k } => {
+                let da = a.distance(p);
+                let db = b.distance(p);
+                let h = (0.5 + 0.5 * (db - da) / k).clamp(0., 1.);
+                db + (da - db) * h - k * h * (1. - h)
+            }
+            Sdf::Repeat { period, inner } => {
+                let wrap = |x: f64, period: f64| x - period * (x / period).round();
+                inner.distance(point(
+                    wrap(p.x, period.x),
+                    wrap(p.y, period.y),
+                    wrap(p.z, period.z),
+                ))
+            }
+            Sdf::Function(f) => f(p),
+        }
+    }
+
+    /// A box the surface is sure to lie within, or an infinite one when we
+    /// cannot tell, as for repetitions and arbitrary functions.
+    pub fn bounds(&self) -> Aabb {
+        let infinity = f64::INFINITY;
+        match self {
+            Sdf::Sphere { radius } => centred_box(vector(*radius, *radius, *radius)),
+            Sdf::Box { half_extents } => centred_box(*half_extents),
+            Sdf::RoundBox {
+                half_extents,
+                radius,
+            } => centred_box(*half_extents + vector(*radius, *radius, *radius)),
+            Sdf::Translate { offset, inner } => inner
+                .bounds()
+                .transform(&translation(offset.x, offset.y, offset.z)),
+            Sdf::Union(a, b) => a.bounds().merge(&b.bounds()),
+            // blending pulls the surface out by at most k / 4
+            Sdf::SmoothUnion { a, b, k } => {
+                let merged = a.bounds().merge(&b.bounds());
+                let grow = vector(k / 4., k / 4., k / 4.);
+                aabb(merged.min - grow, merged.max + grow)
+            }
+            Sdf::Repeat { .. } | Sdf::Function(_) => aabb(
+                point(-infinity, -infinity, -infinity),
+                point(infinity, infinity, infinity),
+            ),
+        }
+    }
+
+    pub fn translate(self, offset: Vector) -> Sdf {
+        Sdf::Translate {
+            offset,
+            inner: Box::new(self),
+        }
+    }
+
+    pub fn union(self, other: Sdf) -> Sdf {
+        Sdf::Union(Box::new(self), Box::new(other))
+    }
+
+    /// Like `union` but blends the two surfaces together over roughly
+    /// distance `k` where they meet.
+    pub fn smooth_union(self, other: Sdf, k: f64) -> Sdf {
+        Sdf::SmoothUnion {
+            a: Box::new(self),
+            b: Box::new(other),
+            k,
+        }
+    }
+
+    /// Infinitely repeats the shape every `period` along each axis.
+    pub fn repeat(self, period: Vector) -> Sdf {
+        Sdf::Repeat {
+            period,
+            inner: Box::new(self),
+        }
+    }
+}
+
+impl fmt::Debug for Sdf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sdf::Sphere { radius } => write!(f, "Sphere({})", radius),
+            Sdf::Box { half_extents } => write!(f, "Box({:?})", half_extents),
+            Sdf::RoundBox {
+                half_extents,
+                radius,
+            } => write!(f, "RoundBox({:?}, {})", half_extents, radius),
+            Sdf::Translate { offset, inner } => write!(f, "Translate({:?}, {:?})", offset, inner),
+            Sdf::Union(a, b) => write!(f, "Union({:?}, {:?})", a, b),
+            Sdf::SmoothUnion { a, b, k } => write!(f, "SmoothUnion({:?}, {:?}, {})", a, b, k),
+            Sdf::Repeat { period, inner } => write!(f, "Repeat({:?}, {:?})", period, inner),
+            Sdf::Function(_) => write!(f, "Function"),
+        }
+    }
+}
+
+fn centred_box(half_extents: Vector) -> Aabb {
+    aabb(
+        point(0., 0., 0.) - half_extents,
+        point(0., 0., 0.) + half_extents,
+    )
+}
+
+fn box_distance(p: Point, half_extents: Vector) -> f64 {
+    let q = vector(
+        p.x.abs() - half_extents.x,
+        p.y.abs() - half_extents.y,
+        p.z.abs() - half_extents.z,
+    );
+    let outside = vector(q.x.max(0.), q.y.max(0.), q.z.max(0.)).magnitude();
+    let inside = q.x.max(q.y).max(q.z).min(0.);
+    outside + inside
+}
+
+/// A shape whose surface is wherever its `Sdf` is zero, found by sphere
+/// tracing: step along the ray by the distance to the nearest surface, and
+/// wherever the distance changes sign, narrow the crossing down to within
+/// `hit_distance` by bisection.
+///
+/// Rays grazing the silhouette take many small steps, so `max_steps` is
+/// generous.
+#[derive(Debug, Clone)]
+pub struct SdfShape {
+    base: ShapeBase,
+    pub sdf: Sdf,
+    pub max_steps: usize,
+    pub max_distance: f64,
+    pub hit_distance: f64,
+}
+
+impl Shape for SdfShape {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        // march in unit steps then convert back, so t stays valid for the
+        // caller's unnormalized ray
+        let length = local_ray.direction.magnitude();
+        let direction = local_ray.direction / length;
+        // only the stretch of ray inside the bounds can reach the surface;
+        // starting at the box also finds the entries behind the origin that
+        // CSG needs to know which side of the surface the ray starts on
+        let bounds = self.sdf.bounds();
+        let (mut t, end) = if bounds.is_finite() {
+            match bounds.entry_exit(&ray(local_ray.origin, direction)) {
+                Some(entry_exit) => entry_exit,
+                None => return intersections(vec![]),
+            }
+        } else {
+            (0., self.max_distance)
+        };
+        let distance = |t: f64| self.sdf.distance(local_ray.origin + direction * t);
+        // the threshold grows with t, as floating point error does
+        let threshold = |t: f64| self.hit_distance * t.abs().max(1.);
+        // a crossing is wherever the distance changes sign, so a ray leaving
+        // the surface, like a shadow ray, never hits where it starts
+        let mut d = distance(t);
+        let mut xs = vec![];
+        for _ in 0..self.max_steps {
+            let next = (t + d.abs().max(threshold(t))).min(end);
+            let next_d = distance(next);
+            if (d < 0.) != (next_d < 0.) {
+                let (mut lo, mut hi) = (t, next);
+                while hi - lo > threshold(hi) {
+                    let mid = (lo + hi) / 2.;
+                    if (distance(mid) < 0.) == (d < 0.) {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                // keep to the side the ray came from, so points nudged off
+                // the surface towards the eye stay on that side
+                xs.push(intersection(lo / length, self));
+            }
+            if next >= end {
+                break;
+            }
+            t = next;
+            d = next_d;
+        }
+        intersections(xs)
+    }
+
+    // Central differences approximate the gradient, which points straight
+    // out of the surface.
+    fn local_normal_at(&self, local_point: Point, _hit: &Intersection) -> Vector {
+        let h = NORMAL_STEP;
+        let d = |dx: f64, dy: f64, dz: f64| self.sdf.distance(local_point + vector(dx, dy, dz));
+        vector(
+            d(h, 0., 0.) - d(-h, 0., 0.),
+            d(0., h, 0.) - d(0., -h, 0.),
+            d(0., 0., h) - d(0., 0., -h),
+        )
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.sdf.bounds()
+    }
+}
+
+const NORMAL_STEP: f64 = 0.0001;
+
+pub fn sdf_shape(sdf: Sdf) -> SdfShape {
+    SdfShape {
+        base: ShapeBase::default(),
+        sdf,
+        max_steps: 1024,
+        max_distance: 1000.,
+        hit_distance: 0.00001,
+    }
+}
+
+pub fn sdf_sphere(radius: f64) -> Sdf {
+    Sdf::Sphere { radius }
+}
+
+pub fn sdf_box(half_extents: Vector) -> Sdf {
+    Sdf::Box { half_extents }
+}
+
+pub fn sdf_round_box(half_extents: Vector, radius: f64) -> Sdf {
+    Sdf::RoundBox {
+        half_extents,
+        radius,
+    }
+}
+
+pub fn sdf_function<F>(f: F) -> Sdf
+where
+    F: Fn(Point) -> f64 + Send + Sync + 'static,
+{
+    Sdf::Function(Arc::new(f))
+}
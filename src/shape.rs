@@ -0,0 +1,557 @@
+use crate::intersection::{Intersection, Intersections};
+use crate::material::Material;
+use crate::matrix::{Matrix, IDENTITY};
+use crate::ray::Ray;
+use crate::transform::{decompose_exactly, interpolate_parts, lerp, Decomposition};
+use crate::triangle::TriangleTest;
+use crate::tuple::{aabb, vector, Aabb, Point, Vector};
+use std::fmt::Debug;
+
+#[cfg(test)]
+mod tests_for_shape {
+    use crate::cube::cube;
+    use crate::group::group;
+    use crate::intersection::{intersection, intersections, Intersections};
+    use crate::material::material;
+    use crate::matrix::IDENTITY;
+    use crate::ray::{ray, ray_at_time, Ray};
+    use crate::shape::*;
+    use crate::sphere::sphere;
+    use crate::transform::{rotation_y, rotation_z, scaling, translation};
+    use crate::tuple::{point, vector, Point, Vector};
+    use std::f64::consts::{FRAC_1_SQRT_2, PI, SQRT_2};
+    use std::sync::Mutex;
+
+    // Records the object-space ray it was asked to intersect so we can
+    // check the common code converted it correctly.
+    #[derive(Debug, Default)]
+    struct TestShape {
+        base: ShapeBase,
+        saved_ray: Mutex<Option<Ray>>,
+    }
+
+    impl Shape for TestShape {
+        fn base(&self) -> &ShapeBase {
+            &self.base
+        }
+
+        fn base_mut(&mut self) -> &mut ShapeBase {
+            &mut self.base
+        }
+
+        fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+            *self.saved_ray.lock().unwrap() = Some(*local_ray);
+            intersections(vec![intersection(1., self)])
+        }
+
+        fn local_normal_at(&self, local_point: Point, _hit: &Intersection) -> Vector {
+            vector(local_point.x, local_point.y, local_point.z)
+        }
+
+        fn bounds(&self) -> Aabb {
+            aabb(point(-1., -1., -1.), point(1., 1., 1.))
+        }
+    }
+
+    fn test_shape() -> TestShape {
+        TestShape::default()
+    }
+
+    #[test]
+    fn its_default_transformation_is_the_identity() {
+        assert_eq!(*test_shape().transform(), IDENTITY);
+    }
+
+    #[test]
+    fn its_transformation_can_be_assigned() {
+        let mut s = test_shape();
+        s.set_transform(translation(2., 3., 4.));
+        assert_eq!(*s.transform(), translation(2., 3., 4.));
+        assert_eq!(*s.base().inverse(), translation(-2., -3., -4.));
+    }
+
+    #[test]
+    fn it_has_a_default_material() {
+        assert_eq!(*test_shape().material(), material());
+    }
+
+    #[test]
+    fn its_material_can_be_assigned() {
+        let mut s = test_shape();
+        let m = Material {
+            ambient: 1.,
+            ..material()
+        };
+        s.set_material(m);
+        assert_eq!(*s.material(), m);
+    }
+
+    #[test]
+    fn intersecting_a_scaled_shape_uses_the_object_space_ray() {
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let mut s = test_shape();
+        s.set_transform(scaling(2., 2., 2.));
+        let xs = s.intersect(&r);
+        let saved = s.saved_ray.lock().unwrap().unwrap();
+        assert_eq!(saved.origin, point(0., 0., -2.5));
+        assert_eq!(saved.direction, vector(0., 0., 0.5));
+        assert!(same_shape(xs[0].object, &s));
+    }
+
+    #[test]
+    fn intersecting_a_translated_shape_uses_the_object_space_ray() {
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let mut s = test_shape();
+        s.set_transform(translation(5., 0., 0.));
+        s.intersect(&r);
+        let saved = s.saved_ray.lock().unwrap().unwrap();
+        assert_eq!(saved.origin, point(-5., 0., -5.));
+        assert_eq!(saved.direction, vector(0., 0., 1.));
+    }
+
+    #[test]
+    fn a_moving_shape_is_intersected_where_it_is_at_the_rays_time() {
+        let mut s = test_shape();
+        s.set_end_transform(Some(translation(10., 0., 0.)));
+        let r = ray_at_time(point(0., 0., -5.), vector(0., 0., 1.), 0.5);
+        let xs = s.intersect(&r);
+        let saved = s.saved_ray.lock().unwrap().unwrap();
+        assert_eq!(saved.origin, point(-5., 0., -5.));
+        assert_abs_diff_eq!(xs[0].time, 0.5);
+    }
+
+    #[test]
+    fn a_moving_shape_has_its_normal_found_at_the_time_of_the_hit() {
+        let mut s = test_shape();
+        s.set_end_transform(Some(translation(0., 2., 0.)));
+        let mut hit = intersection(0., &s);
+        hit.time = 0.5;
+        let n = s.normal_at(point(0., 1. + FRAC_1_SQRT_2, -FRAC_1_SQRT_2), &hit);
+        assert_eq!(n, vector(0., FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+    }
+
+    #[test]
+    fn a_moving_shape_keeps_a_sheared_transform_at_either_end() {
+        let mut s = test_shape();
+        let start = scaling(1., 0.5, 1.) * rotation_z(PI / 5.);
+        s.set_transform(start);
+        s.set_end_transform(Some(translation(4., 0., 0.) * start));
+        let motion = s.base().motion();
+        assert_eq!(motion.at(0.), start);
+        assert_eq!(motion.at(1.), translation(4., 0., 0.) * start);
+        assert_eq!(motion.at(0.5), translation(2., 0., 0.) * start);
+        assert_eq!(motion.inverse_at(0.), start.inverse());
+    }
+
+    #[test]
+    fn a_rotating_shapes_bounds_cover_its_corners_between_samples() {
+        let mut c = cube();
+        c.set_end_transform(Some(rotation_y(100_f64.to_radians())));
+        let r = ray_at_time(point(-5., 0., SQRT_2 - 0.0001), vector(1., 0., 0.), 0.45);
+        assert_eq!(c.intersect(&r).len(), 2);
+        let mut g = group();
+        g.add_child(Box::new(c));
+        assert_eq!(g.intersect(&r).len(), 2);
+    }
+
+    #[test]
+    fn a_moving_shapes_bounds_cover_its_whole_path() {
+        let mut s = test_shape();
+        s.set_end_transform(Some(translation(10., 0., 0.) * rotation_z(PI / 4.)));
+        let b = s.parent_space_bounds();
+        // padded a little for the corners turning between samples
+        assert_abs_diff_eq!(b.min.x, -1., epsilon = 0.01);
+        assert!(b.min.x <= -1.);
+        assert_abs_diff_eq!(b.min.y, -SQRT_2, epsilon = 0.01);
+        assert_abs_diff_eq!(b.max.x, 10. + SQRT_2, epsilon = 0.01);
+    }
+
+    #[test]
+    fn it_knows_whether_it_is_hit_before_a_distance() {
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let s = test_shape();
+        assert!(s.hits_before(&r, 2.));
+        assert!(!s.hits_before(&r, 1.));
+    }
+
+    #[test]
+    fn the_normal_on_a_translated_shape() {
+        let mut s = test_shape();
+        s.set_transform(translation(0., 1., 0.));
+        let n = s.normal_at(
+            point(0., 1. + FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+            &intersection(0., &s),
+        );
+        assert_eq!(n, vector(0., FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+    }
+
+    #[test]
+    fn the_normal_on_a_transformed_shape() {
+        let mut s = test_shape();
+        s.set_transform(scaling(1., 0.5, 1.) * rotation_z(PI / 5.));
+        let n = s.normal_at(
+            point(0., FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+            &intersection(0., &s),
+        );
+        assert_eq!(n, vector(0., 0.97014, -0.24254));
+    }
+
+    #[test]
+    fn different_shapes_can_be_stored_together_as_trait_objects() {
+        let mut shapes: Vec<Box<dyn Shape>> = vec![Box::new(sphere()), Box::new(test_shape())];
+        shapes[0].set_transform(translation(0., 0., 10.));
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        assert_eq!(shapes[0].intersect(&r).len(), 2);
+        assert_eq!(shapes[1].intersect(&r).len(), 1);
+        assert!(same_shape(shapes[0].as_ref(), shapes[0].as_ref()));
+        assert!(!same_shape(shapes[0].as_ref(), shapes[1].as_ref()));
+    }
+
+    #[test]
+    fn its_bounds_in_parent_space_include_its_transform() {
+        let mut s = test_shape();
+        s.set_transform(translation(1., -3., 5.) * scaling(0.5, 2., 4.));
+        let b = s.parent_space_bounds();
+        assert_eq!(b.min, point(0.5, -5., 1.));
+        assert_eq!(b.max, point(1.5, -1., 9.));
+    }
+
+    #[test]
+    fn a_plain_shape_only_includes_itself() {
+        let s1 = test_shape();
+        let s2 = test_shape();
+        assert!(s1.includes(&s1));
+        assert!(!s1.includes(&s2));
+    }
+}
+
+/// A transform that may change while the shutter is open, running from
+/// `start` at time 0 to `end` at time 1. Moving shapes blur in the render.
+#[derive(Debug, Clone)]
+pub struct Motion {
+    start: Matrix,
+    start_inverse: Matrix,
+    end: Option<MotionEnd>,
+}
+
+// Everything about the end of a motion that can be worked out once, rather
+// than for every ray.
+#[derive(Debug, Clone)]
+struct MotionEnd {
+    end: Matrix,
+    end_inverse: Matrix,
+    // Both ends split into translation, rotation and scaling, or `None` if
+    // either has shearing that would be lost, in which case we `lerp`.
+    parts: Option<(Decomposition, Decomposition)>,
+}
+
+impl Motion {
+    pub fn is_moving(&self) -> bool {
+        self.end.is_some()
+    }
+
+    fn set_start(&mut self, start: Matrix) {
+        self.start = start;
+        self.start_inverse = start.inverse();
+        let end = self.end.as_ref().map(|e| e.end);
+        self.set_end(end);
+    }
+
+    fn set_end(&mut self, end: Option<Matrix>) {
+        self.end = end.map(|end| MotionEnd {
+            end,
+            end_inverse: end.inverse(),
+            parts: decompose_exactly(&self.start).zip(decompose_exactly(&end)),
+        });
+    }
+
+    pub fn at(&self, time: f64) -> Matrix {
+        match &self.end {
+            None => self.start,
+            Some(_) if time <= 0. => self.start,
+            Some(e) if time >= 1. => e.end,
+            Some(MotionEnd {
+                parts: Some((start, end)),
+                ..
+            }) => interpolate_parts(start, end, time),
+            Some(e) => lerp(&self.start, &e.end, time),
+        }
+    }
+
+    /// How far the object-space `bounds` can stray from the straight line
+    /// between two of `samples` evenly spaced times: the sag of the arc a
+    /// corner turns through in one step. Blended matrices move in straight
+    /// lines, so only decomposed motions have any.
+    pub fn sag(&self, bounds: &Aabb, samples: usize) -> f64 {
+        let (start, end) = match &self.end {
+            Some(MotionEnd {
+                parts: Some(parts), ..
+            }) => parts,
+            _ => return 0.,
+        };
+        let turn = 2. * start.1.dot(end.1).abs().min(1.).acos();
+        if turn == 0. || bounds.is_empty() {
+            return 0.;
+        }
+        let mut radius: f64 = 0.;
+        for scale in [start.2, end.2] {
+            for x in [bounds.min.x, bounds.max.x] {
+                for y in [bounds.min.y, bounds.max.y] {
+                    for z in [bounds.min.z, bounds.max.z] {
+                        let corner = vector(x * scale.x, y * scale.y, z * scale.z);
+                        radius = radius.max(corner.magnitude());
+                    }
+                }
+            }
+        }
+        radius * (1. - (turn / samples as f64 / 2.).cos())
+    }
+
+    pub fn inverse_at(&self, time: f64) -> Matrix {
+        match &self.end {
+            None => self.start_inverse,
+            Some(_) if time <= 0. => self.start_inverse,
+            Some(e) if time >= 1. => e.end_inverse,
+            Some(_) => self.at(time).inverse(),
+        }
+    }
+}
+
+/// The state every shape shares: its transform and the cached inverses
+/// we need to move rays and normals between world and object space.
+///
+/// Shapes inside groups also keep the transform of each enclosing group,
+/// outermost first, so normals can be carried back up to the world.
+#[derive(Debug, Clone)]
+pub struct ShapeBase {
+    motion: Motion,
+    inverse_transpose: Matrix,
+    parents: Vec<Motion>,
+    material: Material,
+}
+
+impl ShapeBase {
+    pub fn transform(&self) -> &Matrix {
+        &self.motion.start
+    }
+
+    pub fn inverse(&self) -> &Matrix {
+        &self.motion.start_inverse
+    }
+
+    pub fn inverse_transpose(&self) -> &Matrix {
+        &self.inverse_transpose
+    }
+
+    pub fn set_transform(&mut self, transform: Matrix) {
+        self.motion.set_start(transform);
+        self.inverse_transpose = self.motion.start_inverse.transpose();
+    }
+
+    pub fn motion(&self) -> &Motion {
+        &self.motion
+    }
+
+    /// Makes the shape move from its transform as the shutter opens to
+    /// `end` as it closes, or stand still again with `None`.
+    pub fn set_end_transform(&mut self, end: Option<Matrix>) {
+        self.motion.set_end(end);
+    }
+
+    pub fn inverse_at(&self, time: f64) -> Matrix {
+        self.motion.inverse_at(time)
+    }
+
+    pub fn inverse_transpose_at(&self, time: f64) -> Matrix {
+        if self.motion.is_moving() {
+            self.inverse_at(time).transpose()
+        } else {
+            self.inverse_transpose
+        }
+    }
+
+    pub fn parents(&self) -> &[Motion] {
+        &self.parents
+    }
+
+    pub fn set_parents(&mut self, parents: Vec<Motion>) {
+        self.parents = parents;
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    /// The chain to hand our children if we are a group: ours, then us.
+    pub fn parents_for_children(&self) -> Vec<Motion> {
+        let mut parents = self.parents.clone();
+        parents.push(self.motion.clone());
+        parents
+    }
+}
+
+impl Default for ShapeBase {
+    fn default() -> ShapeBase {
+        ShapeBase {
+            motion: Motion {
+                start: IDENTITY,
+                start_inverse: IDENTITY,
+                end: None,
+            },
+            inverse_transpose: IDENTITY,
+            parents: vec![],
+            material: Material::default(),
+        }
+    }
+}
+
+// Rotations can carry corners outside the boxes at the ends of a motion,
+// so moving bounds are sampled along the way, then padded to cover the
+// corners between samples.
+const MOTION_BOUNDS_SAMPLES: usize = 16;
+
+/// Something that can be hit by a ray.
+///
+/// Implementors only deal with object space in `local_intersect` and
+/// `local_normal_at`; the provided methods take care of converting to and
+/// from world space.
+pub trait Shape: Debug + Send + Sync {
+    fn base(&self) -> &ShapeBase;
+
+    fn base_mut(&mut self) -> &mut ShapeBase;
+
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_>;
+
+    fn local_normal_at(&self, local_point: Point, hit: &Intersection) -> Vector;
+
+    /// A box around the shape in object space.
+    fn bounds(&self) -> Aabb;
+
+    fn transform(&self) -> &Matrix {
+        self.base().transform()
+    }
+
+    fn set_transform(&mut self, transform: Matrix) {
+        self.base_mut().set_transform(transform)
+    }
+
+    fn material(&self) -> &Material {
+        self.base().material()
+    }
+
+    fn set_material(&mut self, material: Material) {
+        self.base_mut().set_material(material)
+    }
+
+    /// Makes the shape move to `end` over the time the shutter is open.
+    fn set_end_transform(&mut self, end: Option<Matrix>) {
+        self.base_mut().set_end_transform(end)
+    }
+
+    /// A box around the shape in its parent's space, which is what a group
+    /// needs to decide whether a ray could hit it. For a moving shape this
+    /// covers everywhere it goes while the shutter is open.
+    fn parent_space_bounds(&self) -> Aabb {
+        let motion = self.base().motion();
+        if !motion.is_moving() {
+            return self.bounds().transform(self.transform());
+        }
+        let bounds = self.bounds();
+        let swept = (0..=MOTION_BOUNDS_SAMPLES)
+            .map(|i| motion.at(i as f64 / MOTION_BOUNDS_SAMPLES as f64))
+            .fold(Aabb::default(), |all, m| all.merge(&bounds.transform(&m)));
+        let sag = motion.sag(&bounds, MOTION_BOUNDS_SAMPLES);
+        let pad = vector(sag, sag, sag);
+        aabb(swept.min - pad, swept.max + pad)
+    }
+
+    /// Called when the shape is put in a group; groups override this to
+    /// pass the chain on to their own children.
+    fn set_parents(&mut self, parents: Vec<Motion>) {
+        self.base_mut().set_parents(parents)
+    }
+
+    /// Picks how any triangles in the shape are intersected. Groups pass it
+    /// on to their children, so a whole mesh can be switched at once.
+    fn set_triangle_test(&mut self, _test: TriangleTest) {}
+
+    /// Whether `other` is this shape or, for composite shapes, one of the
+    /// shapes inside it.
+    fn includes(&self, other: &dyn Shape) -> bool {
+        self as *const Self as *const () == other as *const dyn Shape as *const ()
+    }
+
+    // The ray is in our parent's space, whether that is the world or an
+    // enclosing group, so only our own transform needs undoing.
+    fn intersect(&self, ray: &Ray) -> Intersections<'_> {
+        self.local_intersect(&ray.transform(&self.base().inverse_at(ray.time)))
+            .at_time(ray.time)
+    }
+
+    /// The nearest intersection at or in front of the ray's origin, which
+    /// is all a camera or reflection ray needs. Shapes holding many others
+    /// override `local_hit` to skip whatever lies beyond the nearest so far.
+    fn hit(&self, ray: &Ray) -> Option<Intersection<'_>> {
+        let mut hit = self.local_hit(&ray.transform(&self.base().inverse_at(ray.time)))?;
+        hit.time = ray.time;
+        Some(hit)
+    }
+
+    fn local_hit(&self, local_ray: &Ray) -> Option<Intersection<'_>> {
+        self.local_intersect(local_ray).hit().copied()
+    }
+
+    /// Whether the ray hits anything between its origin and `max_t`, which
+    /// is all a shadow ray needs to know. Shapes holding many others
+    /// override `local_hits_before` to stop at the first such hit.
+    fn hits_before(&self, ray: &Ray, max_t: f64) -> bool {
+        self.local_hits_before(&ray.transform(&self.base().inverse_at(ray.time)), max_t)
+    }
+
+    fn local_hits_before(&self, local_ray: &Ray, max_t: f64) -> bool {
+        self.local_intersect(local_ray)
+            .iter()
+            .any(|i| i.t >= 0. && i.t < max_t)
+    }
+
+    // Both take the time since shapes and the groups holding them may be
+    // somewhere different at each moment the shutter is open.
+    fn world_to_object(&self, world_point: Point, time: f64) -> Point {
+        let parent_point = self
+            .base()
+            .parents()
+            .iter()
+            .fold(world_point, |p, parent| parent.inverse_at(time) * p);
+        self.base().inverse_at(time) * parent_point
+    }
+
+    fn normal_to_world(&self, local_normal: Vector, time: f64) -> Vector {
+        let normal = (self.base().inverse_transpose_at(time) * local_normal).normalize();
+        self.base()
+            .parents()
+            .iter()
+            .rev()
+            .fold(normal, |n, parent| {
+                (parent.inverse_at(time).transpose() * n).normalize()
+            })
+    }
+
+    // `hit` is passed through so shapes like smooth triangles can use where
+    // on their surface the ray struck, not just the point.
+    fn normal_at(&self, world_point: Point, hit: &Intersection) -> Vector {
+        let local_point = self.world_to_object(world_point, hit.time);
+        let local_normal = self.local_normal_at(local_point, hit);
+        self.normal_to_world(local_normal, hit.time)
+    }
+}
+
+/// Shapes are compared by identity: two spheres with the same transform
+/// are still different objects.
+pub fn same_shape(a: &dyn Shape, b: &dyn Shape) -> bool {
+    // compare addresses only, vtable pointers are not guaranteed unique
+    a as *const dyn Shape as *const () == b as *const dyn Shape as *const ()
+}
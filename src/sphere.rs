@@ -0,0 +1,219 @@
+use crate::intersection::{intersection, intersections, Intersection, Intersections};
+use crate::ray::Ray;
+use crate::shape::{Shape, ShapeBase};
+use crate::tuple::{aabb, point, Aabb, Point, Vector};
+
+#[cfg(test)]
+mod tests_for_sphere {
+    use crate::canvas::canvas;
+    use crate::color::{BLACK, RED};
+    use crate::matrix::IDENTITY;
+    use crate::ray::ray;
+    use crate::shape::same_shape;
+    use crate::sphere::*;
+    use crate::transform::{rotation_z, scaling, translation};
+    use crate::tuple::{point, vector};
+    use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+    #[test]
+    fn a_ray_intersects_it_at_two_points() {
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let s = sphere();
+        let xs = s.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_abs_diff_eq!(xs[0].t, 4.);
+        assert_abs_diff_eq!(xs[1].t, 6.);
+    }
+
+    #[test]
+    fn a_ray_intersects_it_at_a_tangent() {
+        let r = ray(point(0., 1., -5.), vector(0., 0., 1.));
+        let s = sphere();
+        let xs = s.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_abs_diff_eq!(xs[0].t, 5.);
+        assert_abs_diff_eq!(xs[1].t, 5.);
+    }
+
+    #[test]
+    fn a_ray_misses_it() {
+        let r = ray(point(0., 2., -5.), vector(0., 0., 1.));
+        assert!(sphere().intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn a_ray_originating_inside_intersects_behind_and_in_front() {
+        let r = ray(point(0., 0., 0.), vector(0., 0., 1.));
+        let s = sphere();
+        let xs = s.intersect(&r);
+        assert_abs_diff_eq!(xs[0].t, -1.);
+        assert_abs_diff_eq!(xs[1].t, 1.);
+    }
+
+    #[test]
+    fn a_ray_intersects_it_when_it_is_behind_the_ray() {
+        let r = ray(point(0., 0., 5.), vector(0., 0., 1.));
+        let s = sphere();
+        let xs = s.intersect(&r);
+        assert_abs_diff_eq!(xs[0].t, -6.);
+        assert_abs_diff_eq!(xs[1].t, -4.);
+    }
+
+    #[test]
+    fn its_intersections_refer_to_it() {
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let s = sphere();
+        let xs = s.intersect(&r);
+        assert!(same_shape(xs[0].object, &s));
+        assert!(same_shape(xs[1].object, &s));
+    }
+
+    #[test]
+    fn its_default_transformation_is_the_identity() {
+        assert_eq!(*sphere().transform(), IDENTITY);
+    }
+
+    #[test]
+    fn its_transformation_can_be_changed() {
+        let mut s = sphere();
+        let t = translation(2., 3., 4.);
+        s.set_transform(t);
+        assert_eq!(*s.transform(), t);
+    }
+
+    #[test]
+    fn a_scaled_sphere_is_intersected_in_object_space() {
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let mut s = sphere();
+        s.set_transform(scaling(2., 2., 2.));
+        let xs = s.intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_abs_diff_eq!(xs[0].t, 3.);
+        assert_abs_diff_eq!(xs[1].t, 7.);
+    }
+
+    #[test]
+    fn a_translated_sphere_can_be_missed() {
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let mut s = sphere();
+        s.set_transform(translation(5., 0., 0.));
+        assert!(s.intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn its_normal_on_each_axis() {
+        let s = sphere();
+        assert_eq!(
+            s.normal_at(point(1., 0., 0.), &intersection(0., &s)),
+            vector(1., 0., 0.)
+        );
+        assert_eq!(
+            s.normal_at(point(0., 1., 0.), &intersection(0., &s)),
+            vector(0., 1., 0.)
+        );
+        assert_eq!(
+            s.normal_at(point(0., 0., 1.), &intersection(0., &s)),
+            vector(0., 0., 1.)
+        );
+    }
+
+    #[test]
+    fn its_normal_at_a_nonaxial_point_is_normalized() {
+        let s = sphere();
+        let third = f64::sqrt(3.) / 3.;
+        let n = s.normal_at(point(third, third, third), &intersection(0., &s));
+        assert_eq!(n, vector(third, third, third));
+        assert_eq!(n, n.normalize());
+    }
+
+    #[test]
+    fn its_normal_when_translated() {
+        let mut s = sphere();
+        s.set_transform(translation(0., 1., 0.));
+        let n = s.normal_at(
+            point(0., 1. + FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+            &intersection(0., &s),
+        );
+        assert_eq!(n, vector(0., FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+    }
+
+    #[test]
+    fn its_normal_when_transformed() {
+        let mut s = sphere();
+        s.set_transform(scaling(1., 0.5, 1.) * rotation_z(PI / 5.));
+        let root2_2 = f64::sqrt(2.) / 2.;
+        let n = s.normal_at(point(0., root2_2, -root2_2), &intersection(0., &s));
+        assert_eq!(n, vector(0., 0.97014, -0.24254));
+    }
+
+    #[test]
+    fn it_can_be_rendered_as_a_silhouette_on_a_canvas() {
+        let ray_origin = point(0., 0., -5.);
+        let wall_z = 10.;
+        let wall_size = 7.;
+        let canvas_pixels = 20;
+        let pixel_size = wall_size / canvas_pixels as f64;
+        let half = wall_size / 2.;
+        let mut c = canvas(canvas_pixels, canvas_pixels);
+        let s = sphere();
+        for y in 0..canvas_pixels {
+            let world_y = half - pixel_size * y as f64;
+            for x in 0..canvas_pixels {
+                let world_x = -half + pixel_size * x as f64;
+                let target = point(world_x, world_y, wall_z);
+                let r = ray(ray_origin, (target - ray_origin).normalize());
+                if s.intersect(&r).hit().is_some() {
+                    c.write_pixel(x, y, RED);
+                }
+            }
+        }
+        assert_eq!(c[10][10], RED);
+        assert_eq!(c[0][0], BLACK);
+        assert_eq!(c[19][19], BLACK);
+    }
+}
+
+/// A unit sphere centred on the origin; use `set_transform` to move,
+/// resize or squash it.
+#[derive(Debug, Clone, Default)]
+pub struct Sphere {
+    base: ShapeBase,
+}
+
+impl Shape for Sphere {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        let sphere_to_ray = local_ray.origin - point(0., 0., 0.);
+        let a = local_ray.direction.dot(local_ray.direction);
+        let b = 2. * local_ray.direction.dot(sphere_to_ray);
+        let c = sphere_to_ray.dot(sphere_to_ray) - 1.;
+        let discriminant = b * b - 4. * a * c;
+        if discriminant < 0. {
+            return intersections(vec![]);
+        }
+        let root = discriminant.sqrt();
+        intersections(vec![
+            intersection((-b - root) / (2. * a), self),
+            intersection((-b + root) / (2. * a), self),
+        ])
+    }
+
+    fn local_normal_at(&self, local_point: Point, _hit: &Intersection) -> Vector {
+        local_point - point(0., 0., 0.)
+    }
+
+    fn bounds(&self) -> Aabb {
+        aabb(point(-1., -1., -1.), point(1., 1., 1.))
+    }
+}
+
+pub fn sphere() -> Sphere {
+    Sphere::default()
+}
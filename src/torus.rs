@@ -0,0 +1,179 @@
+use crate::f64_helpers::solve_quartic;
+use crate::intersection::{intersection, intersections, Intersection, Intersections};
+use crate::ray::Ray;
+use crate::shape::{Shape, ShapeBase};
+use crate::tuple::{aabb, point, vector, Aabb, Point, Vector};
+
+#[cfg(test)]
+mod tests_for_torus {
+    use crate::f64_helpers::EPS;
+    use crate::intersection::intersection;
+    use crate::ray::ray;
+    use crate::shape::same_shape;
+    use crate::torus::*;
+    use crate::transform::rotation_x;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn it_has_a_major_and_minor_radius() {
+        let t = torus(2., 0.5);
+        assert_abs_diff_eq!(t.major_radius, 2.);
+        assert_abs_diff_eq!(t.minor_radius, 0.5);
+    }
+
+    #[test]
+    fn a_ray_through_both_sides_of_the_ring_hits_four_times() {
+        let t = torus(1., 0.25);
+        let r = ray(point(-5., 0., 0.), vector(1., 0., 0.));
+        let xs = t.local_intersect(&r);
+        let ts: Vec<f64> = xs.iter().map(|i| i.t).collect();
+        assert_eq!(ts.len(), 4);
+        let expected = [3.75, 4.25, 5.75, 6.25];
+        for (got, want) in ts.iter().zip(expected.iter()) {
+            assert_abs_diff_eq!(got, want, epsilon = EPS);
+        }
+        assert!(same_shape(xs[0].object, &t));
+    }
+
+    #[test]
+    fn a_ray_through_the_hole_misses() {
+        let t = torus(1., 0.25);
+        let r = ray(point(0., 5., 0.), vector(0., -1., 0.));
+        assert!(t.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn a_ray_down_through_the_tube_hits_twice() {
+        let t = torus(1., 0.25);
+        let r = ray(point(1., 5., 0.), vector(0., -1., 0.));
+        let xs = t.local_intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_abs_diff_eq!(xs[0].t, 4.75, epsilon = EPS);
+        assert_abs_diff_eq!(xs[1].t, 5.25, epsilon = EPS);
+    }
+
+    #[test]
+    fn a_ray_from_far_away_is_still_accurate() {
+        let t = torus(1., 0.25);
+        let r = ray(point(-1e4, 0., 0.), vector(1., 0., 0.));
+        let xs = t.local_intersect(&r);
+        assert_eq!(xs.len(), 4);
+        assert_abs_diff_eq!(xs[0].t, 1e4 - 1.25, epsilon = EPS);
+        assert_abs_diff_eq!(xs[3].t, 1e4 + 1.25, epsilon = EPS);
+    }
+
+    #[test]
+    fn a_ray_with_an_unnormalized_direction_keeps_its_t_values() {
+        let t = torus(1., 0.25);
+        let r = ray(point(-5., 0., 0.), vector(2., 0., 0.));
+        let xs = t.local_intersect(&r);
+        assert_abs_diff_eq!(xs[0].t, 1.875, epsilon = EPS);
+    }
+
+    #[test]
+    fn a_ray_above_the_ring_misses() {
+        let t = torus(1., 0.25);
+        let r = ray(point(-5., 0.3, 0.), vector(1., 0., 0.));
+        assert!(t.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn its_normal_points_away_from_the_tube_centre() {
+        let t = torus(1., 0.25);
+        let cases = [
+            (point(1.25, 0., 0.), vector(1., 0., 0.)),
+            (point(0.75, 0., 0.), vector(-1., 0., 0.)),
+            (point(0., 0.25, 1.), vector(0., 1., 0.)),
+            (point(0., -0.25, -1.), vector(0., -1., 0.)),
+        ];
+        for (p, expected) in cases.iter() {
+            let n = t.local_normal_at(*p, &intersection(0., &t)).normalize();
+            assert_eq!(n, *expected);
+        }
+    }
+
+    #[test]
+    fn it_can_be_stood_on_its_edge() {
+        let mut t = torus(1., 0.25);
+        t.set_transform(rotation_x(PI / 2.));
+        let r = ray(point(0., -5., 0.), vector(0., 1., 0.));
+        assert_eq!(t.intersect(&r).len(), 4);
+    }
+}
+
+/// A ring around the y axis: a tube of `minor_radius` swept around a
+/// circle of `major_radius` in the xz plane.
+#[derive(Debug, Clone)]
+pub struct Torus {
+    base: ShapeBase,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+}
+
+impl Default for Torus {
+    fn default() -> Torus {
+        torus(1., 0.25)
+    }
+}
+
+impl Shape for Torus {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        // Solving from a unit direction, starting at the point on the ray
+        // closest to the centre, keeps the quartic well conditioned for
+        // rays that start far away.
+        let length = local_ray.direction.magnitude();
+        let d = local_ray.direction / length;
+        let t_closest = -(local_ray.origin - point(0., 0., 0.)).dot(d);
+        let o = local_ray.origin + d * t_closest;
+
+        let major_squared = self.major_radius * self.major_radius;
+        let minor_squared = self.minor_radius * self.minor_radius;
+        let o_vec = o - point(0., 0., 0.);
+        let f = o_vec.dot(d);
+        let e = o_vec.dot(o_vec) - major_squared - minor_squared;
+        let four_major_squared = 4. * major_squared;
+
+        let roots = solve_quartic(
+            1.,
+            4. * f,
+            2. * e + 4. * f * f + four_major_squared * d.y * d.y,
+            4. * f * e + 2. * four_major_squared * o.y * d.y,
+            e * e - four_major_squared * (minor_squared - o.y * o.y),
+        );
+        let xs = roots
+            .into_iter()
+            .map(|t| intersection((t + t_closest) / length, self))
+            .collect();
+        intersections(xs)
+    }
+
+    fn local_normal_at(&self, local_point: Point, _hit: &Intersection) -> Vector {
+        // away from the nearest point on the circle running through the tube
+        let ring = vector(local_point.x, 0., local_point.z).normalize() * self.major_radius;
+        local_point - (point(0., 0., 0.) + ring)
+    }
+
+    fn bounds(&self) -> Aabb {
+        let r = self.major_radius + self.minor_radius;
+        aabb(
+            point(-r, -self.minor_radius, -r),
+            point(r, self.minor_radius, r),
+        )
+    }
+}
+
+pub fn torus(major_radius: f64, minor_radius: f64) -> Torus {
+    Torus {
+        base: ShapeBase::default(),
+        major_radius,
+        minor_radius,
+    }
+}
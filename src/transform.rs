@@ -0,0 +1,415 @@
+// Our convention is to put each module's tests above the code they exercise.
+#![allow(clippy::items_after_test_module)]
+
+use crate::matrix::{matrix4, Matrix};
+use crate::quaternion::{rotation_quaternion, Quaternion};
+use crate::tuple::{vector, Point, Vector};
+
+#[cfg(test)]
+mod tests_for_transform {
+    use crate::transform::*;
+    use crate::tuple::{point, vector};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn translation_moves_a_point() {
+        let transform = translation(5., -3., 2.);
+        let p = point(-3., 4., 5.);
+        assert_eq!(transform * p, point(2., 1., 7.));
+    }
+
+    #[test]
+    fn inverse_translation_moves_a_point_backwards() {
+        let inv = translation(5., -3., 2.).inverse();
+        let p = point(-3., 4., 5.);
+        assert_eq!(inv * p, point(-8., 7., 3.));
+    }
+
+    #[test]
+    fn translation_does_not_affect_a_vector() {
+        let transform = translation(5., -3., 2.);
+        let v = vector(-3., 4., 5.);
+        assert_eq!(transform * v, v);
+    }
+
+    #[test]
+    fn scaling_applies_to_points_and_vectors() {
+        let transform = scaling(2., 3., 4.);
+        assert_eq!(transform * point(-4., 6., 8.), point(-8., 18., 32.));
+        assert_eq!(transform * vector(-4., 6., 8.), vector(-8., 18., 32.));
+        let inv = transform.inverse();
+        assert_eq!(inv * vector(-4., 6., 8.), vector(-2., 2., 2.));
+    }
+
+    #[test]
+    fn reflection_is_scaling_by_a_negative_value() {
+        let transform = scaling(-1., 1., 1.);
+        assert_eq!(transform * point(2., 3., 4.), point(-2., 3., 4.));
+    }
+
+    #[test]
+    fn rotation_x_rotates_a_point_around_the_x_axis() {
+        let p = point(0., 1., 0.);
+        let half_quarter = rotation_x(PI / 4.);
+        let full_quarter = rotation_x(PI / 2.);
+        let root2_2 = f64::sqrt(2.) / 2.;
+        assert_eq!(half_quarter * p, point(0., root2_2, root2_2));
+        assert_eq!(full_quarter * p, point(0., 0., 1.));
+        assert_eq!(half_quarter.inverse() * p, point(0., root2_2, -root2_2));
+    }
+
+    #[test]
+    fn rotation_y_rotates_a_point_around_the_y_axis() {
+        let p = point(0., 0., 1.);
+        let root2_2 = f64::sqrt(2.) / 2.;
+        assert_eq!(rotation_y(PI / 4.) * p, point(root2_2, 0., root2_2));
+        assert_eq!(rotation_y(PI / 2.) * p, point(1., 0., 0.));
+    }
+
+    #[test]
+    fn rotation_z_rotates_a_point_around_the_z_axis() {
+        let p = point(0., 1., 0.);
+        let root2_2 = f64::sqrt(2.) / 2.;
+        assert_eq!(rotation_z(PI / 4.) * p, point(-root2_2, root2_2, 0.));
+        assert_eq!(rotation_z(PI / 2.) * p, point(-1., 0., 0.));
+    }
+
+    #[test]
+    fn shearing_moves_each_component_in_proportion_to_the_others() {
+        let p = point(2., 3., 4.);
+        assert_eq!(shearing(1., 0., 0., 0., 0., 0.) * p, point(5., 3., 4.));
+        assert_eq!(shearing(0., 1., 0., 0., 0., 0.) * p, point(6., 3., 4.));
+        assert_eq!(shearing(0., 0., 1., 0., 0., 0.) * p, point(2., 5., 4.));
+        assert_eq!(shearing(0., 0., 0., 1., 0., 0.) * p, point(2., 7., 4.));
+        assert_eq!(shearing(0., 0., 0., 0., 1., 0.) * p, point(2., 3., 6.));
+        assert_eq!(shearing(0., 0., 0., 0., 0., 1.) * p, point(2., 3., 7.));
+    }
+
+    #[test]
+    fn individual_transformations_are_applied_in_sequence() {
+        let p = point(1., 0., 1.);
+        let a = rotation_x(PI / 2.);
+        let b = scaling(5., 5., 5.);
+        let c = translation(10., 5., 7.);
+        let p2 = a * p;
+        assert_eq!(p2, point(1., -1., 0.));
+        let p3 = b * p2;
+        assert_eq!(p3, point(5., -5., 0.));
+        let p4 = c * p3;
+        assert_eq!(p4, point(15., 0., 7.));
+    }
+
+    #[test]
+    fn chained_transformations_must_be_applied_in_reverse_order() {
+        let p = point(1., 0., 1.);
+        let t = translation(10., 5., 7.) * scaling(5., 5., 5.) * rotation_x(PI / 2.);
+        assert_eq!(t * p, point(15., 0., 7.));
+    }
+
+    #[test]
+    fn the_fluent_builder_applies_transformations_in_the_order_written() {
+        let p = point(1., 0., 1.);
+        let t = Transform::identity()
+            .rotate_x(PI / 2.)
+            .scale(5., 5., 5.)
+            .translate(10., 5., 7.);
+        assert_eq!(t * p, point(15., 0., 7.));
+        assert_eq!(
+            t,
+            translation(10., 5., 7.) * scaling(5., 5., 5.) * rotation_x(PI / 2.)
+        );
+    }
+
+    #[test]
+    fn the_fluent_builder_covers_every_transformation() {
+        let t = Transform::identity()
+            .rotate_y(PI / 2.)
+            .rotate_z(PI / 2.)
+            .shear(1., 0., 0., 0., 0., 0.);
+        let expected = shearing(1., 0., 0., 0., 0., 0.) * rotation_z(PI / 2.) * rotation_y(PI / 2.);
+        assert_eq!(t, expected);
+    }
+
+    #[test]
+    fn a_transform_can_be_taken_apart() {
+        let t = translation(1., 2., 3.) * rotation_y(PI / 3.) * scaling(2., 0.5, 4.);
+        let (moved, rotation, scale) = decompose(&t);
+        assert_eq!(moved, vector(1., 2., 3.));
+        assert_eq!(rotation.to_matrix(), rotation_y(PI / 3.));
+        assert_eq!(scale, vector(2., 0.5, 4.));
+    }
+
+    #[test]
+    fn a_mirroring_transform_keeps_a_proper_rotation() {
+        let t = scaling(-1., 1., 1.);
+        let (_, rotation, scale) = decompose(&t);
+        assert_abs_diff_eq!(rotation.to_matrix().determinant(), 1., epsilon = 0.00001);
+        assert_eq!(interpolate(&t, &t, 0.5), t);
+        assert_eq!(scale.x * scale.y * scale.z, -1.);
+    }
+
+    #[test]
+    fn interpolating_blends_each_part_separately() {
+        let start = translation(0., 0., 0.);
+        let end = translation(10., 0., 0.) * rotation_z(PI / 2.) * scaling(3., 3., 3.);
+        assert_eq!(interpolate(&start, &end, 0.), start);
+        assert_eq!(interpolate(&start, &end, 1.), end);
+        assert_eq!(
+            interpolate(&start, &end, 0.5),
+            translation(5., 0., 0.) * rotation_z(PI / 4.) * scaling(2., 2., 2.)
+        );
+    }
+
+    #[test]
+    fn interpolating_returns_the_ends_exactly() {
+        let start = scaling(1., 0.5, 1.) * rotation_z(PI / 5.);
+        let end = translation(3., 0., 0.) * shearing(1., 0., 0., 0., 0., 0.);
+        assert_eq!(interpolate(&start, &end, 0.), start);
+        assert_eq!(interpolate(&start, &end, 1.), end);
+    }
+
+    #[test]
+    fn only_translation_rotation_and_scaling_decompose_exactly() {
+        let t = translation(1., 2., 3.) * rotation_y(PI / 3.) * scaling(2., 0.5, 4.);
+        assert_eq!(compose(&decompose_exactly(&t).unwrap()), t);
+        assert!(decompose_exactly(&(scaling(1., 0.5, 1.) * rotation_z(PI / 5.))).is_none());
+        assert!(decompose_exactly(&shearing(1., 0., 0., 0., 0., 0.)).is_none());
+    }
+
+    #[test]
+    fn transforms_that_do_not_decompose_are_blended_element_by_element() {
+        let start = scaling(1., 0.5, 1.) * rotation_z(PI / 5.);
+        let end = translation(4., 0., 0.) * start;
+        let halfway = interpolate(&start, &end, 0.5);
+        assert_eq!(halfway, translation(2., 0., 0.) * start);
+        assert_eq!(halfway, lerp(&start, &end, 0.5));
+    }
+
+    #[test]
+    fn the_default_view_looks_down_negative_z() {
+        let t = view_transform(point(0., 0., 0.), point(0., 0., -1.), vector(0., 1., 0.));
+        assert_eq!(t, Matrix::identity());
+    }
+
+    #[test]
+    fn looking_down_positive_z_mirrors_the_scene() {
+        let t = view_transform(point(0., 0., 0.), point(0., 0., 1.), vector(0., 1., 0.));
+        assert_eq!(t, scaling(-1., 1., -1.));
+    }
+
+    #[test]
+    fn the_view_transform_moves_the_world_not_the_eye() {
+        let t = view_transform(point(0., 0., 8.), point(0., 0., 0.), vector(0., 1., 0.));
+        assert_eq!(t, translation(0., 0., -8.));
+    }
+
+    #[test]
+    fn an_arbitrary_view_transform() {
+        let t = view_transform(point(1., 3., 2.), point(4., -2., 8.), vector(1., 1., 0.));
+        let expected = matrix4([
+            [-0.50709, 0.50709, 0.67612, -2.36643],
+            [0.76772, 0.60609, 0.12122, -2.82843],
+            [-0.35857, 0.59761, -0.71714, 0.],
+            [0., 0., 0., 1.],
+        ]);
+        assert_eq!(t, expected);
+    }
+}
+
+pub fn translation(x: f64, y: f64, z: f64) -> Matrix {
+    matrix4([
+        [1., 0., 0., x],
+        [0., 1., 0., y],
+        [0., 0., 1., z],
+        [0., 0., 0., 1.],
+    ])
+}
+
+pub fn scaling(x: f64, y: f64, z: f64) -> Matrix {
+    matrix4([
+        [x, 0., 0., 0.],
+        [0., y, 0., 0.],
+        [0., 0., z, 0.],
+        [0., 0., 0., 1.],
+    ])
+}
+
+/// Rotation by `radians` around the x axis, clockwise looking towards the origin.
+pub fn rotation_x(radians: f64) -> Matrix {
+    let (sin, cos) = radians.sin_cos();
+    matrix4([
+        [1., 0., 0., 0.],
+        [0., cos, -sin, 0.],
+        [0., sin, cos, 0.],
+        [0., 0., 0., 1.],
+    ])
+}
+
+pub fn rotation_y(radians: f64) -> Matrix {
+    let (sin, cos) = radians.sin_cos();
+    matrix4([
+        [cos, 0., sin, 0.],
+        [0., 1., 0., 0.],
+        [-sin, 0., cos, 0.],
+        [0., 0., 0., 1.],
+    ])
+}
+
+pub fn rotation_z(radians: f64) -> Matrix {
+    let (sin, cos) = radians.sin_cos();
+    matrix4([
+        [cos, -sin, 0., 0.],
+        [sin, cos, 0., 0.],
+        [0., 0., 1., 0.],
+        [0., 0., 0., 1.],
+    ])
+}
+
+/// Each argument moves one component in proportion to another,
+/// e.g. `x_y` moves x in proportion to y.
+pub fn shearing(x_y: f64, x_z: f64, y_x: f64, y_z: f64, z_x: f64, z_y: f64) -> Matrix {
+    matrix4([
+        [1., x_y, x_z, 0.],
+        [y_x, 1., y_z, 0.],
+        [z_x, z_y, 1., 0.],
+        [0., 0., 0., 1.],
+    ])
+}
+
+/// Transformations are plain 4x4 matrices; the alias reads better when
+/// chaining, e.g. `Transform::identity().rotate_x(a).scale(2., 2., 2.)`.
+pub type Transform = Matrix;
+
+// Each builder method left-multiplies, so transformations apply in the
+// order they are chained rather than the reverse order of a raw product.
+impl Matrix {
+    pub fn translate(self, x: f64, y: f64, z: f64) -> Matrix {
+        translation(x, y, z) * self
+    }
+
+    pub fn scale(self, x: f64, y: f64, z: f64) -> Matrix {
+        scaling(x, y, z) * self
+    }
+
+    pub fn rotate_x(self, radians: f64) -> Matrix {
+        rotation_x(radians) * self
+    }
+
+    pub fn rotate_y(self, radians: f64) -> Matrix {
+        rotation_y(radians) * self
+    }
+
+    pub fn rotate_z(self, radians: f64) -> Matrix {
+        rotation_z(radians) * self
+    }
+
+    pub fn shear(self, x_y: f64, x_z: f64, y_x: f64, y_z: f64, z_x: f64, z_y: f64) -> Matrix {
+        shearing(x_y, x_z, y_x, y_z, z_x, z_y) * self
+    }
+}
+
+/// The translation, rotation and scaling `decompose` splits a transform
+/// into.
+pub type Decomposition = (Vector, Quaternion, Vector);
+
+/// Splits an affine transform made of translation, rotation and scaling
+/// into those three parts, so that `m = translation * rotation * scaling`.
+/// Shearing has no place in the result and is lost.
+pub fn decompose(m: &Matrix) -> Decomposition {
+    let moved = vector(m[(0, 3)], m[(1, 3)], m[(2, 3)]);
+    let column = |c: usize| vector(m[(0, c)], m[(1, c)], m[(2, c)]);
+    let mut scale = vector(
+        column(0).magnitude(),
+        column(1).magnitude(),
+        column(2).magnitude(),
+    );
+    // a mirror image cannot be a rotation, so fold it into the scaling
+    if m.submatrix(3, 3).determinant() < 0. {
+        scale.x = -scale.x;
+    }
+    let mut rotation = Matrix::identity();
+    for c in 0..3 {
+        for r in 0..3 {
+            rotation[(r, c)] = m[(r, c)] / scale[c];
+        }
+    }
+    (moved, rotation_quaternion(&rotation), scale)
+}
+
+/// Puts a transform back together from its parts: the inverse of
+/// `decompose` for transforms without shearing.
+pub fn compose(&(moved, rotation, scale): &Decomposition) -> Matrix {
+    translation(moved.x, moved.y, moved.z)
+        * rotation.to_matrix()
+        * scaling(scale.x, scale.y, scale.z)
+}
+
+/// Decomposes `m` only if its parts put it back together again, which is
+/// not the case for shearing or scaling applied after a rotation.
+pub fn decompose_exactly(m: &Matrix) -> Option<Decomposition> {
+    let parts = decompose(m);
+    if compose(&parts) == *m {
+        Some(parts)
+    } else {
+        None
+    }
+}
+
+/// The transform a fraction `t` of the way between two decomposed ones.
+/// Moving and scaling are blended in a straight line and rotations turn at
+/// an even rate, so a spinning object stays rigid.
+pub fn interpolate_parts(start: &Decomposition, end: &Decomposition, t: f64) -> Matrix {
+    let (start_moved, start_rotation, start_scale) = *start;
+    let (end_moved, end_rotation, end_scale) = *end;
+    compose(&(
+        start_moved * (1. - t) + end_moved * t,
+        start_rotation.slerp(end_rotation, t),
+        start_scale * (1. - t) + end_scale * t,
+    ))
+}
+
+/// Blends each element of two matrices in a straight line. Rotations shrink
+/// part way through, but it works for any pair of transforms.
+pub fn lerp(start: &Matrix, end: &Matrix, t: f64) -> Matrix {
+    let mut m = Matrix::identity();
+    for r in 0..4 {
+        for c in 0..4 {
+            m[(r, c)] = start[(r, c)] * (1. - t) + end[(r, c)] * t;
+        }
+    }
+    m
+}
+
+/// The transform a fraction `t` of the way from `start` to `end`, exactly
+/// `start` at 0 and `end` at 1.
+///
+/// Transforms made only of translation, rotation and scaling go through
+/// `interpolate_parts`. Anything else, such as shearing, falls back to
+/// `lerp` rather than losing the parts `decompose` cannot represent.
+pub fn interpolate(start: &Matrix, end: &Matrix, t: f64) -> Matrix {
+    if t <= 0. {
+        return *start;
+    }
+    if t >= 1. {
+        return *end;
+    }
+    match (decompose_exactly(start), decompose_exactly(end)) {
+        (Some(start_parts), Some(end_parts)) => interpolate_parts(&start_parts, &end_parts, t),
+        _ => lerp(start, end, t),
+    }
+}
+
+/// Orients the world as seen by an eye at `from` looking towards `to`, with
+/// `up` roughly upwards. The result moves the world, not the eye.
+pub fn view_transform(from: Point, to: Point, up: Vector) -> Matrix {
+    let forward = (to - from).normalize();
+    let left = forward.cross(up.normalize());
+    let true_up = left.cross(forward);
+    let orientation = matrix4([
+        [left.x, left.y, left.z, 0.],
+        [true_up.x, true_up.y, true_up.z, 0.],
+        [-forward.x, -forward.y, -forward.z, 0.],
+        [0., 0., 0., 1.],
+    ]);
+    orientation * translation(-from.x, -from.y, -from.z)
+}
@@ -0,0 +1,429 @@
+use crate::f64_helpers::EPS;
+use crate::intersection::{intersection_with_uv, intersections, Intersection, Intersections};
+use crate::ray::Ray;
+use crate::shape::{Shape, ShapeBase};
+use crate::tuple::{Aabb, Point, Vector};
+
+#[cfg(test)]
+mod tests_for_triangle {
+    use crate::group::group;
+    use crate::intersection::intersection_with_uv;
+    use crate::ray::ray;
+    use crate::shape::same_shape;
+    use crate::triangle::*;
+    use crate::tuple::{point, vector};
+
+    fn default_triangle() -> Triangle {
+        triangle(point(0., 1., 0.), point(-1., 0., 0.), point(1., 0., 0.))
+    }
+
+    fn default_smooth_triangle() -> SmoothTriangle {
+        smooth_triangle(
+            point(0., 1., 0.),
+            point(-1., 0., 0.),
+            point(1., 0., 0.),
+            vector(0., 1., 0.),
+            vector(-1., 0., 0.),
+            vector(1., 0., 0.),
+        )
+    }
+
+    #[test]
+    fn it_precomputes_its_edges_and_normal() {
+        let t = default_triangle();
+        assert_eq!(t.p1, point(0., 1., 0.));
+        assert_eq!(t.p2, point(-1., 0., 0.));
+        assert_eq!(t.p3, point(1., 0., 0.));
+        assert_eq!(t.e1, vector(-1., -1., 0.));
+        assert_eq!(t.e2, vector(1., -1., 0.));
+        assert_eq!(t.normal, vector(0., 0., -1.));
+    }
+
+    #[test]
+    fn its_normal_is_the_same_everywhere() {
+        let t = default_triangle();
+        let hit = intersection_with_uv(0., &t, 0., 0.);
+        assert_eq!(t.local_normal_at(point(0., 0.5, 0.), &hit), t.normal);
+        assert_eq!(t.local_normal_at(point(-0.5, 0.75, 0.), &hit), t.normal);
+        assert_eq!(t.local_normal_at(point(0.5, 0.25, 0.), &hit), t.normal);
+    }
+
+    #[test]
+    fn a_ray_parallel_to_it_misses() {
+        let t = default_triangle();
+        let r = ray(point(0., -1., -2.), vector(0., 1., 0.));
+        assert!(t.local_intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn a_ray_misses_each_edge() {
+        let t = default_triangle();
+        let origins = [point(1., 1., -2.), point(-1., 1., -2.), point(0., -1., -2.)];
+        for origin in origins.iter() {
+            let r = ray(*origin, vector(0., 0., 1.));
+            assert!(t.local_intersect(&r).is_empty());
+        }
+    }
+
+    #[test]
+    fn a_ray_strikes_it() {
+        let t = default_triangle();
+        let r = ray(point(0., 0.5, -2.), vector(0., 0., 1.));
+        let xs = t.local_intersect(&r);
+        assert_eq!(xs.len(), 1);
+        assert_abs_diff_eq!(xs[0].t, 2.);
+        assert!(same_shape(xs[0].object, &t));
+    }
+
+    #[test]
+    fn a_smooth_triangle_keeps_its_vertex_normals() {
+        let tri = default_smooth_triangle();
+        assert_eq!(tri.n1, vector(0., 1., 0.));
+        assert_eq!(tri.n2, vector(-1., 0., 0.));
+        assert_eq!(tri.n3, vector(1., 0., 0.));
+    }
+
+    #[test]
+    fn an_intersection_with_a_smooth_triangle_stores_u_and_v() {
+        let tri = default_smooth_triangle();
+        let r = ray(point(-0.2, 0.3, -2.), vector(0., 0., 1.));
+        let xs = tri.local_intersect(&r);
+        assert_abs_diff_eq!(xs[0].u, 0.45, epsilon = EPS);
+        assert_abs_diff_eq!(xs[0].v, 0.25, epsilon = EPS);
+    }
+
+    #[test]
+    fn a_smooth_triangle_interpolates_its_normal_with_u_and_v() {
+        let tri = default_smooth_triangle();
+        let hit = intersection_with_uv(1., &tri, 0.45, 0.25);
+        let n = tri.normal_at(point(0., 0., 0.), &hit);
+        assert_eq!(n, vector(-0.5547, 0.83205, 0.));
+    }
+
+    #[test]
+    fn the_watertight_test_agrees_with_moller_trumbore() {
+        let t = default_triangle();
+        let rays = [
+            ray(point(-0.2, 0.3, -2.), vector(0., 0., 1.)),
+            ray(point(3., -1., 4.), vector(-2.8, 1.3125, -3.5)),
+            ray(point(0., 0.5, 2.), vector(0.1, 0.05, -1.)),
+        ];
+        for r in rays.iter() {
+            let (t1, u1, v1) = moller_trumbore(r, t.p1, t.e1, t.e2).unwrap();
+            let (t2, u2, v2) = watertight(r, t.p1, t.p2, t.p3).unwrap();
+            assert_abs_diff_eq!(t1, t2, epsilon = EPS);
+            assert_abs_diff_eq!(u1, u2, epsilon = EPS);
+            assert_abs_diff_eq!(v1, v2, epsilon = EPS);
+        }
+    }
+
+    #[test]
+    fn the_watertight_test_misses_like_moller_trumbore() {
+        let t = default_triangle();
+        let origins = [point(1., 1., -2.), point(-1., 1., -2.), point(0., -1., -2.)];
+        for origin in origins.iter() {
+            let r = ray(*origin, vector(0., 0., 1.));
+            assert!(watertight(&r, t.p1, t.p2, t.p3).is_none());
+        }
+        let parallel = ray(point(0., -1., -2.), vector(0., 1., 0.));
+        assert!(watertight(&parallel, t.p1, t.p2, t.p3).is_none());
+    }
+
+    #[test]
+    fn the_watertight_test_drops_hits_too_close_to_tell_which_side() {
+        let t = default_triangle();
+        let r = ray(point(0., 0.5, 0.), vector(0., 0., 1.));
+        assert!(moller_trumbore(&r, t.p1, t.e1, t.e2).is_some());
+        assert!(watertight(&r, t.p1, t.p2, t.p3).is_none());
+    }
+
+    #[test]
+    fn a_watertight_mesh_has_no_gaps_along_shared_edges() {
+        let a = point(0.1, 0.3, 0.7);
+        let b = point(2.3, 0.1, 1.9);
+        let c = point(1.7, 2.9, 0.3);
+        let d = point(-0.4, 2.2, 1.1);
+        let mut mesh = group();
+        mesh.add_child(Box::new(triangle(a, b, c)));
+        mesh.add_child(Box::new(smooth_triangle(
+            a,
+            c,
+            d,
+            vector(0., 0., 1.),
+            vector(0., 0., 1.),
+            vector(0., 0., 1.),
+        )));
+        mesh.set_triangle_test(TriangleTest::Watertight);
+        let origin = point(0.37, 1.13, -7.9);
+        for i in 0..=1000 {
+            let s = i as f64 / 1000.;
+            let r = ray(origin, (a + (c - a) * s) - origin);
+            assert!(!mesh.intersect(&r).is_empty());
+        }
+        for corner in [a, b, c, d].iter() {
+            assert!(!mesh.intersect(&ray(origin, *corner - origin)).is_empty());
+        }
+    }
+
+    #[test]
+    fn triangles_start_out_using_moller_trumbore() {
+        let mut t = default_triangle();
+        assert_eq!(t.test, TriangleTest::MollerTrumbore);
+        t.set_triangle_test(TriangleTest::Watertight);
+        assert_eq!(t.test, TriangleTest::Watertight);
+    }
+}
+
+/// How a ray is tested against a triangle.
+///
+/// Möller–Trumbore is quick, but rounding can let a ray slip between two
+/// triangles sharing an edge. The watertight test never does, at some cost,
+/// so it suits meshes that must be closed such as CAD models.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TriangleTest {
+    #[default]
+    MollerTrumbore,
+    Watertight,
+}
+
+/// A flat triangle, the building block of imported meshes.
+///
+/// Edges and the face normal are precomputed because they are needed for
+/// every ray.
+#[derive(Debug, Clone)]
+pub struct Triangle {
+    base: ShapeBase,
+    pub p1: Point,
+    pub p2: Point,
+    pub p3: Point,
+    pub e1: Vector,
+    pub e2: Vector,
+    pub normal: Vector,
+    pub test: TriangleTest,
+}
+
+impl Shape for Triangle {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        let hit = match self.test {
+            TriangleTest::MollerTrumbore => moller_trumbore(local_ray, self.p1, self.e1, self.e2),
+            TriangleTest::Watertight => watertight(local_ray, self.p1, self.p2, self.p3),
+        };
+        match hit {
+            Some((t, u, v)) => intersections(vec![intersection_with_uv(t, self, u, v)]),
+            None => intersections(vec![]),
+        }
+    }
+
+    fn set_triangle_test(&mut self, test: TriangleTest) {
+        self.test = test;
+    }
+
+    fn local_normal_at(&self, _local_point: Point, _hit: &Intersection) -> Vector {
+        self.normal
+    }
+
+    fn bounds(&self) -> Aabb {
+        triangle_bounds(self.p1, self.p2, self.p3)
+    }
+}
+
+/// A triangle whose normal is blended from a normal at each vertex, so a
+/// coarse mesh can still look smoothly curved.
+#[derive(Debug, Clone)]
+pub struct SmoothTriangle {
+    base: ShapeBase,
+    pub p1: Point,
+    pub p2: Point,
+    pub p3: Point,
+    pub n1: Vector,
+    pub n2: Vector,
+    pub n3: Vector,
+    pub e1: Vector,
+    pub e2: Vector,
+    pub test: TriangleTest,
+}
+
+impl Shape for SmoothTriangle {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        let hit = match self.test {
+            TriangleTest::MollerTrumbore => moller_trumbore(local_ray, self.p1, self.e1, self.e2),
+            TriangleTest::Watertight => watertight(local_ray, self.p1, self.p2, self.p3),
+        };
+        match hit {
+            Some((t, u, v)) => intersections(vec![intersection_with_uv(t, self, u, v)]),
+            None => intersections(vec![]),
+        }
+    }
+
+    fn set_triangle_test(&mut self, test: TriangleTest) {
+        self.test = test;
+    }
+
+    fn local_normal_at(&self, _local_point: Point, hit: &Intersection) -> Vector {
+        self.n2 * hit.u + self.n3 * hit.v + self.n1 * (1. - hit.u - hit.v)
+    }
+
+    fn bounds(&self) -> Aabb {
+        triangle_bounds(self.p1, self.p2, self.p3)
+    }
+}
+
+/// Möller–Trumbore ray/triangle test, returning `(t, u, v)` on a hit where
+/// `u` and `v` are the barycentric weights of the second and third vertex.
+pub fn moller_trumbore(
+    local_ray: &Ray,
+    p1: Point,
+    e1: Vector,
+    e2: Vector,
+) -> Option<(f64, f64, f64)> {
+    let dir_cross_e2 = local_ray.direction.cross(e2);
+    let det = e1.dot(dir_cross_e2);
+    if det.abs() < EPS {
+        return None;
+    }
+    let f = 1. / det;
+    let p1_to_origin = local_ray.origin - p1;
+    let u = f * p1_to_origin.dot(dir_cross_e2);
+    if !(0. ..=1.).contains(&u) {
+        return None;
+    }
+    let origin_cross_e1 = p1_to_origin.cross(e1);
+    let v = f * local_ray.direction.dot(origin_cross_e1);
+    if v < 0. || u + v > 1. {
+        return None;
+    }
+    Some((f * e2.dot(origin_cross_e1), u, v))
+}
+
+/// Woop, Benthin and Wald's watertight ray/triangle test, returning the same
+/// `(t, u, v)` as `moller_trumbore`.
+///
+/// The vertices are moved so the ray starts at the origin and runs along
+/// +z, leaving a 2D test of which side of each edge the origin lies. An edge
+/// shared by two triangles then gives exactly opposite answers in each, so
+/// any ray through it hits at least one. Hits closer to the origin than the
+/// rounding error in `t` are dropped, as it is unknowable which side they
+/// are on.
+pub fn watertight(local_ray: &Ray, p1: Point, p2: Point, p3: Point) -> Option<(f64, f64, f64)> {
+    let d = local_ray.direction;
+    // permute so z is the largest component of the direction, swapping x
+    // and y if that flips the winding
+    let kz = (0..3)
+        .max_by(|&a, &b| d[a].abs().total_cmp(&d[b].abs()))
+        .unwrap();
+    let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+    if d[kz] < 0. {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+    if d[kz] == 0. {
+        return None;
+    }
+    let shear_x = d[kx] / d[kz];
+    let shear_y = d[ky] / d[kz];
+    let shear_z = 1. / d[kz];
+    let sheared = |p: Point| {
+        let p = p - local_ray.origin;
+        (
+            p[kx] - shear_x * p[kz],
+            p[ky] - shear_y * p[kz],
+            shear_z * p[kz],
+        )
+    };
+    let (a, b, c) = (sheared(p1), sheared(p2), sheared(p3));
+
+    // each edge function is twice the area of the origin and that edge
+    let e1 = c.0 * b.1 - c.1 * b.0;
+    let e2 = a.0 * c.1 - a.1 * c.0;
+    let e3 = b.0 * a.1 - b.1 * a.0;
+    if (e1 < 0. || e2 < 0. || e3 < 0.) && (e1 > 0. || e2 > 0. || e3 > 0.) {
+        return None;
+    }
+    let det = e1 + e2 + e3;
+    if det == 0. {
+        return None;
+    }
+    let t = (e1 * a.2 + e2 * b.2 + e3 * c.2) / det;
+
+    // bound the rounding error in t, following Pharr, Jakob and Humphreys
+    let max_x = a.0.abs().max(b.0.abs()).max(c.0.abs());
+    let max_y = a.1.abs().max(b.1.abs()).max(c.1.abs());
+    let max_z = a.2.abs().max(b.2.abs()).max(c.2.abs());
+    let max_e = e1.abs().max(e2.abs()).max(e3.abs());
+    let delta_x = gamma(5) * (max_x + max_z);
+    let delta_y = gamma(5) * (max_y + max_z);
+    let delta_z = gamma(3) * max_z;
+    let delta_e = 2. * (gamma(2) * max_x * max_y + delta_y * max_x + delta_x * max_y);
+    let delta_t = 3. * (gamma(3) * max_e * max_z + delta_e * max_z + delta_z * max_e) / det.abs();
+    if t.abs() <= delta_t {
+        return None;
+    }
+    Some((t, e2 / det, e3 / det))
+}
+
+/// The most relative error `n` rounded floating point operations can build
+/// up.
+fn gamma(n: u32) -> f64 {
+    let unit_roundoff = f64::EPSILON / 2.;
+    n as f64 * unit_roundoff / (1. - n as f64 * unit_roundoff)
+}
+
+fn triangle_bounds(p1: Point, p2: Point, p3: Point) -> Aabb {
+    let mut bounds = Aabb::default();
+    bounds.add_point(p1);
+    bounds.add_point(p2);
+    bounds.add_point(p3);
+    bounds
+}
+
+pub fn triangle(p1: Point, p2: Point, p3: Point) -> Triangle {
+    let e1 = p2 - p1;
+    let e2 = p3 - p1;
+    Triangle {
+        base: ShapeBase::default(),
+        p1,
+        p2,
+        p3,
+        e1,
+        e2,
+        normal: e2.cross(e1).normalize(),
+        test: TriangleTest::default(),
+    }
+}
+
+pub fn smooth_triangle(
+    p1: Point,
+    p2: Point,
+    p3: Point,
+    n1: Vector,
+    n2: Vector,
+    n3: Vector,
+) -> SmoothTriangle {
+    SmoothTriangle {
+        base: ShapeBase::default(),
+        p1,
+        p2,
+        p3,
+        n1,
+        n2,
+        n3,
+        e1: p2 - p1,
+        e2: p3 - p1,
+        test: TriangleTest::default(),
+    }
+}
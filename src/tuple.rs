@@ -1,4 +1,6 @@
 use crate::f64_helpers::EPS;
+use crate::matrix::Matrix;
+use crate::ray::Ray;
 use std::cmp::PartialEq;
 use std::f64;
 use std::ops::{Add, Div, Index, Mul, Neg, Sub};
@@ -115,6 +117,7 @@ mod tests_for_vector {
     use super::*;
     use crate::f64_helpers::EPS;
     use crate::tuple::vector;
+    use std::f64::consts::FRAC_1_SQRT_2;
 
     #[test]
     fn it_can_be_accessed_by_components() {
@@ -295,6 +298,162 @@ mod tests_for_vector {
         assert!(cross(v1, v2) == vector(-1., 2., -1.));
         assert!(cross(v2, v1) == vector(1., -2., 1.));
     }
+
+    #[test]
+    fn it_can_be_reflected_at_45_degrees() {
+        let v = vector(1., -1., 0.);
+        let n = vector(0., 1., 0.);
+        assert!(v.reflect(n) == vector(1., 1., 0.));
+    }
+
+    #[test]
+    fn it_can_be_reflected_off_a_slanted_surface() {
+        let v = vector(0., -1., 0.);
+        let n = vector(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.);
+        assert!(v.reflect(n) == vector(1., 0., 0.));
+    }
+}
+
+#[cfg(test)]
+mod tests_for_aabb {
+    use crate::bvh::bvh;
+    use crate::group::group;
+    use crate::ray::ray;
+    use crate::shape::Shape;
+    use crate::transform::{rotation_x, rotation_y, translation};
+    use crate::triangle::triangle;
+    use crate::tuple::*;
+    use std::f64::consts::{PI, SQRT_2};
+
+    #[test]
+    fn it_starts_empty() {
+        let b = Aabb::default();
+        assert!(b.is_empty());
+        assert_eq!(b.min.x, f64::INFINITY);
+        assert_eq!(b.max.x, -f64::INFINITY);
+    }
+
+    #[test]
+    fn it_grows_to_fit_added_points() {
+        let mut b = Aabb::default();
+        b.add_point(point(-5., 2., 0.));
+        b.add_point(point(7., 0., -3.));
+        assert_eq!(b.min, point(-5., 0., -3.));
+        assert_eq!(b.max, point(7., 2., 0.));
+    }
+
+    #[test]
+    fn two_boxes_can_be_merged() {
+        let b1 = aabb(point(-5., -2., 0.), point(7., 4., 4.));
+        let b2 = aabb(point(8., -7., -2.), point(14., 2., 8.));
+        let merged = b1.merge(&b2);
+        assert_eq!(merged.min, point(-5., -7., -2.));
+        assert_eq!(merged.max, point(14., 4., 8.));
+    }
+
+    #[test]
+    fn it_knows_which_points_it_contains() {
+        let b = aabb(point(5., -2., 0.), point(11., 4., 7.));
+        assert!(b.contains(point(5., -2., 0.)));
+        assert!(b.contains(point(8., 1., 3.)));
+        assert!(!b.contains(point(3., 0., 3.)));
+        assert!(!b.contains(point(8., 1., 8.)));
+    }
+
+    #[test]
+    fn it_knows_which_boxes_it_contains() {
+        let b = aabb(point(5., -2., 0.), point(11., 4., 7.));
+        assert!(b.contains_box(&aabb(point(6., -1., 1.), point(10., 3., 6.))));
+        assert!(!b.contains_box(&aabb(point(4., -3., -1.), point(10., 3., 6.))));
+    }
+
+    #[test]
+    fn transforming_it_bounds_all_eight_corners() {
+        let b = aabb(point(-1., -1., -1.), point(1., 1., 1.));
+        let t = b.transform(&(rotation_x(PI / 4.) * rotation_y(PI / 4.)));
+        assert_eq!(t.min, point(-SQRT_2, -1.70711, -1.70711));
+        assert_eq!(t.max, point(SQRT_2, 1.70711, 1.70711));
+    }
+
+    #[test]
+    fn transforming_an_infinite_box_keeps_it_infinite() {
+        let plane = aabb(
+            point(-f64::INFINITY, 0., -f64::INFINITY),
+            point(f64::INFINITY, 0., f64::INFINITY),
+        );
+        let t = plane.transform(&translation(1., 2., 3.));
+        assert_eq!(t.min.x, -f64::INFINITY);
+        assert_eq!(t.min.y, 2.);
+        assert_eq!(t.max.z, f64::INFINITY);
+    }
+
+    #[test]
+    fn a_ray_can_be_tested_against_it() {
+        let b = aabb(point(5., -2., 0.), point(11., 4., 7.));
+        let hits = [
+            (point(15., 1., 2.), vector(-1., 0., 0.)),
+            (point(-5., -1., 4.), vector(1., 0., 0.)),
+            (point(7., 6., 5.), vector(0., -1., 0.)),
+            (point(8., 2., 12.), vector(0., 0., -1.)),
+            (point(8., 1., 3.5), vector(0., 0., 1.)),
+        ];
+        for &(origin, direction) in hits.iter() {
+            assert!(b.intersects(&ray(origin, direction)));
+        }
+        let misses = [
+            (point(9., -1., -8.), vector(2., 4., 6.)),
+            (point(8., 3., -4.), vector(6., 2., 4.)),
+            (point(9., -1., -8.), vector(4., 6., 2.)),
+            (point(4., 0., 9.), vector(0., 0., -1.)),
+            (point(8., 6., -1.), vector(0., -1., 0.)),
+            (point(12., 5., 4.), vector(-1., 0., 0.)),
+        ];
+        for &(origin, direction) in misses.iter() {
+            assert!(!b.intersects(&ray(origin, direction)));
+        }
+    }
+
+    #[test]
+    fn it_knows_where_a_ray_enters_and_leaves() {
+        let b = aabb(point(-1., -1., -1.), point(1., 1., 1.));
+        let r = ray(point(0., 0.5, -5.), vector(0., 0., 2.));
+        assert_eq!(b.entry_exit(&r), Some((2., 3.)));
+        let r = ray(point(0., 5., -5.), vector(0., 0., 1.));
+        assert_eq!(b.entry_exit(&r), None);
+    }
+
+    #[test]
+    fn a_shallow_ray_is_not_mistaken_for_one_parallel_to_the_box() {
+        let t = triangle(
+            point(1000., 0., -10.),
+            point(3000., 0., -10.),
+            point(2000., 0., 10.),
+        );
+        let r = ray(point(0., 0.01, 0.), vector(1., -5e-6, 0.));
+        assert_eq!(t.intersect(&r).len(), 1);
+        assert!(t.bounds().intersects(&r));
+        let mut g = group();
+        g.add_child(Box::new(t.clone()));
+        assert_eq!(g.intersect(&r).len(), 1);
+        assert_eq!(bvh(vec![Box::new(t)]).intersect(&r).len(), 1);
+    }
+
+    #[test]
+    fn a_ray_along_a_face_of_the_box_is_inside_it() {
+        let b = aabb(point(-1., -1., -1.), point(1., 1., 1.));
+        let r = ray(point(-5., 1., 0.), vector(1., 0., 0.));
+        assert_eq!(b.entry_exit(&r), Some((4., 6.)));
+        let r = ray(point(-5., 1.5, 0.), vector(1., 0., 0.));
+        assert_eq!(b.entry_exit(&r), None);
+    }
+
+    #[test]
+    fn it_has_a_centre_and_surface_area() {
+        let b = aabb(point(-1., 0., 2.), point(1., 3., 6.));
+        assert_eq!(b.centre(), point(0., 1.5, 4.));
+        assert_abs_diff_eq!(b.surface_area(), 2. * (2. * 3. + 3. * 4. + 4. * 2.));
+        assert_abs_diff_eq!(Aabb::default().surface_area(), 0.);
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -435,11 +594,18 @@ impl Vector {
             self.x * other.y - self.y * other.x,
         )
     }
+
+    /// Bounces this vector off a surface with the given normal.
+    pub fn reflect(self, normal: Vector) -> Vector {
+        self - normal * 2. * self.dot(normal)
+    }
 }
 
 impl PartialEq for Vector {
     fn eq(&self, other: &Vector) -> bool {
-        self.x == other.x && self.y == other.y && self.z == other.z
+        abs_diff_eq!(self.x, other.x, epsilon = EPS)
+            && abs_diff_eq!(self.y, other.y, epsilon = EPS)
+            && abs_diff_eq!(self.z, other.z, epsilon = EPS)
     }
 }
 // Should not derive Eq as our f64's could be NaN-y and NaN != NaN
@@ -514,6 +680,172 @@ impl Div<f64> for Vector {
     }
 }
 
+/// Where a ray enters and leaves the slab between `min` and `max` on one
+/// axis, the building block of every ray/box test.
+pub fn check_slab(origin: f64, direction: f64, min: f64, max: f64) -> (f64, f64) {
+    let tmin_numerator = min - origin;
+    let tmax_numerator = max - origin;
+    let (tmin, tmax) = if direction.abs() >= EPS {
+        (tmin_numerator / direction, tmax_numerator / direction)
+    } else {
+        (
+            tmin_numerator * f64::INFINITY,
+            tmax_numerator * f64::INFINITY,
+        )
+    };
+    if tmin > tmax {
+        (tmax, tmin)
+    } else {
+        (tmin, tmax)
+    }
+}
+
+// Like `check_slab`, but dividing by even the tiniest direction so a box
+// never culls a shallow ray that the shape inside it would hit. A ray with
+// no direction along the axis gets infinite t, or NaN if it starts exactly
+// on a face, which counts as inside.
+fn slab_span(origin: f64, direction: f64, min: f64, max: f64) -> (f64, f64) {
+    let tmin = (min - origin) / direction;
+    let tmax = (max - origin) / direction;
+    if tmin.is_nan() || tmax.is_nan() {
+        if origin >= min && origin <= max {
+            (-f64::INFINITY, f64::INFINITY)
+        } else {
+            (f64::INFINITY, -f64::INFINITY)
+        }
+    } else if tmin > tmax {
+        (tmax, tmin)
+    } else {
+        (tmin, tmax)
+    }
+}
+
+/// An axis-aligned bounding box, used to skip shapes a ray cannot hit.
+///
+/// The default box is empty (`min` above `max`) so that adding the first
+/// point or box gives exactly that point or box. Shapes that go on forever
+/// have infinite bounds.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Aabb {
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn add_point(&mut self, p: Point) {
+        self.min = point(
+            self.min.x.min(p.x),
+            self.min.y.min(p.y),
+            self.min.z.min(p.z),
+        );
+        self.max = point(
+            self.max.x.max(p.x),
+            self.max.y.max(p.y),
+            self.max.z.max(p.z),
+        );
+    }
+
+    pub fn merge(&self, other: &Aabb) -> Aabb {
+        let mut merged = *self;
+        merged.add_point(other.min);
+        merged.add_point(other.max);
+        merged
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        (self.min.x..=self.max.x).contains(&p.x)
+            && (self.min.y..=self.max.y).contains(&p.y)
+            && (self.min.z..=self.max.z).contains(&p.z)
+    }
+
+    pub fn contains_box(&self, other: &Aabb) -> bool {
+        self.contains(other.min) && self.contains(other.max)
+    }
+
+    /// The box around this one once transformed, found by transforming all
+    /// eight corners since a rotated box is no longer axis-aligned.
+    pub fn transform(&self, m: &Matrix) -> Aabb {
+        let mut transformed = Aabb::default();
+        if self.is_empty() {
+            return transformed;
+        }
+        for &x in [self.min.x, self.max.x].iter() {
+            for &y in [self.min.y, self.max.y].iter() {
+                for &z in [self.min.z, self.max.z].iter() {
+                    // skip zero terms rather than multiply them out, since
+                    // 0 * infinity would turn infinite bounds into NaN
+                    let row = |r: usize| {
+                        [x, y, z, 1.]
+                            .iter()
+                            .enumerate()
+                            .filter(|&(c, _)| m[(r, c)] != 0.)
+                            .map(|(c, v)| m[(r, c)] * v)
+                            .sum()
+                    };
+                    transformed.add_point(point(row(0), row(1), row(2)));
+                }
+            }
+        }
+        transformed
+    }
+
+    /// The `t` at which the ray enters and leaves the box, if it passes
+    /// through it anywhere along its length, including behind its origin.
+    pub fn entry_exit(&self, r: &Ray) -> Option<(f64, f64)> {
+        let (xtmin, xtmax) = slab_span(r.origin.x, r.direction.x, self.min.x, self.max.x);
+        let (ytmin, ytmax) = slab_span(r.origin.y, r.direction.y, self.min.y, self.max.y);
+        let (ztmin, ztmax) = slab_span(r.origin.z, r.direction.z, self.min.z, self.max.z);
+        let tmin = xtmin.max(ytmin).max(ztmin);
+        let tmax = xtmax.min(ytmax).min(ztmax);
+        if tmin <= tmax {
+            Some((tmin, tmax))
+        } else {
+            None
+        }
+    }
+
+    pub fn intersects(&self, r: &Ray) -> bool {
+        self.entry_exit(r).is_some()
+    }
+
+    pub fn centre(&self) -> Point {
+        point(
+            (self.min.x + self.max.x) / 2.,
+            (self.min.y + self.max.y) / 2.,
+            (self.min.z + self.max.z) / 2.,
+        )
+    }
+
+    /// Proportional to the chance a random ray hits the box, which is what
+    /// makes it the measure of choice when building hierarchies of boxes.
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.;
+        }
+        let size = self.max - self.min;
+        2. * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    pub fn is_finite(&self) -> bool {
+        [self.min, self.max]
+            .iter()
+            .all(|p| p.x.is_finite() && p.y.is_finite() && p.z.is_finite())
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Aabb {
+        aabb(
+            point(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            point(-f64::INFINITY, -f64::INFINITY, -f64::INFINITY),
+        )
+    }
+}
+
 // trait InOORange {
 //     fn in_oo_range(self, begin: Self, end: Self) -> bool;
 // }
@@ -625,11 +957,15 @@ impl Div<f64> for Vector {
 // }
 
 pub fn point(x: f64, y: f64, z: f64) -> Point {
-    Point { x: x, y: y, z: z }
+    Point { x, y, z }
 }
 
 pub fn vector(x: f64, y: f64, z: f64) -> Vector {
-    Vector { x: x, y: y, z: z }
+    Vector { x, y, z }
+}
+
+pub fn aabb(min: Point, max: Point) -> Aabb {
+    Aabb { min, max }
 }
 
 pub fn magnitude(v: Vector) -> f64 {
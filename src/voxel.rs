@@ -0,0 +1,452 @@
+use crate::intersection::{intersection_with_normal, intersections, Intersection, Intersections};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::shape::{Shape, ShapeBase};
+use crate::tuple::{aabb, check_slab, point, vector, Aabb, Point, Vector};
+
+#[cfg(test)]
+mod tests_for_voxel {
+    use crate::color::{color, WHITE};
+    use crate::f64_helpers::EPS;
+    use crate::light::point_light;
+    use crate::material::material;
+    use crate::ray::ray;
+    use crate::shape::same_shape;
+    use crate::transform::scaling;
+    use crate::voxel::*;
+    use crate::world::world;
+
+    fn ts_and_normals(g: &VoxelGrid, r: &Ray) -> Vec<(f64, Vector)> {
+        g.local_intersect(r)
+            .iter()
+            .map(|i| (i.t, i.normal.unwrap()))
+            .collect()
+    }
+
+    fn centre_voxel() -> VoxelGrid {
+        let mut g = voxel_grid(3, 3, 3);
+        g.set(1, 1, 1, 7);
+        g
+    }
+
+    #[test]
+    fn it_starts_empty() {
+        let g = voxel_grid(2, 3, 4);
+        assert_eq!(g.size(), (2, 3, 4));
+        assert_eq!(g.get(1, 2, 3), 0);
+        assert_eq!(g.bounds().max, point(2., 3., 4.));
+    }
+
+    #[test]
+    fn its_voxels_can_be_set() {
+        let g = centre_voxel();
+        assert_eq!(g.get(1, 1, 1), 7);
+        assert_eq!(g.get(1, 1, 2), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn it_panics_outside_the_grid() {
+        voxel_grid(2, 2, 2).get(2, 0, 0);
+    }
+
+    #[test]
+    fn a_ray_through_a_voxel_enters_and_leaves_through_faces() {
+        let g = centre_voxel();
+        let r = ray(point(1.5, 1.5, -5.), vector(0., 0., 1.));
+        let hits = ts_and_normals(&g, &r);
+        assert_eq!(hits.len(), 2);
+        assert_abs_diff_eq!(hits[0].0, 6.);
+        assert_eq!(hits[0].1, vector(0., 0., -1.));
+        assert_abs_diff_eq!(hits[1].0, 7.);
+        assert_eq!(hits[1].1, vector(0., 0., 1.));
+        assert!(same_shape(g.local_intersect(&r)[0].object, &g));
+    }
+
+    #[test]
+    fn neighbouring_solid_voxels_have_no_faces_between_them() {
+        let mut g = centre_voxel();
+        g.set(1, 1, 2, 3);
+        let r = ray(point(1.5, 1.5, -5.), vector(0., 0., 1.));
+        let ts: Vec<f64> = ts_and_normals(&g, &r).iter().map(|h| h.0).collect();
+        assert_eq!(ts, vec![6., 8.]);
+    }
+
+    #[test]
+    fn a_diagonal_ray_steps_along_whichever_axis_is_nearest() {
+        let mut g = voxel_grid(3, 3, 1);
+        g.set(2, 2, 0, 1);
+        let r = ray(point(-0.5, 0.2, 0.5), vector(1., 1., 0.));
+        let hits = ts_and_normals(&g, &r);
+        assert_eq!(hits.len(), 2);
+        assert_abs_diff_eq!(hits[0].0, 2.5, epsilon = EPS);
+        assert_eq!(hits[0].1, vector(-1., 0., 0.));
+        assert_abs_diff_eq!(hits[1].0, 2.8, epsilon = EPS);
+        assert_eq!(hits[1].1, vector(0., 1., 0.));
+    }
+
+    #[test]
+    fn solid_voxels_on_the_edge_of_the_grid_are_closed_off() {
+        let mut g = voxel_grid(1, 1, 1);
+        g.set(0, 0, 0, 1);
+        let r = ray(point(0.5, 5., 0.5), vector(0., -1., 0.));
+        let hits = ts_and_normals(&g, &r);
+        assert_abs_diff_eq!(hits[0].0, 4.);
+        assert_eq!(hits[0].1, vector(0., 1., 0.));
+        assert_abs_diff_eq!(hits[1].0, 5.);
+        assert_eq!(hits[1].1, vector(0., -1., 0.));
+    }
+
+    #[test]
+    fn a_ray_starting_inside_a_voxel_hits_it_behind_and_ahead() {
+        let g = centre_voxel();
+        let r = ray(point(1.5, 1.5, 1.5), vector(1., 0., 0.));
+        let ts: Vec<f64> = ts_and_normals(&g, &r).iter().map(|h| h.0).collect();
+        assert_eq!(ts, vec![-0.5, 0.5]);
+    }
+
+    #[test]
+    fn a_ray_can_miss() {
+        let g = centre_voxel();
+        assert!(g
+            .local_intersect(&ray(point(0.5, 0.5, -5.), vector(0., 0., 1.)))
+            .is_empty());
+        assert!(g
+            .local_intersect(&ray(point(5., 5., -5.), vector(0., 0., 1.)))
+            .is_empty());
+    }
+
+    #[test]
+    fn its_hits_are_shaded_from_the_palette() {
+        let mut g = voxel_grid(3, 1, 1);
+        g.set(0, 0, 0, 1);
+        g.set(1, 0, 0, 2);
+        g.set(2, 0, 0, 9);
+        let red = Material {
+            color: color(1., 0., 0.),
+            ..material()
+        };
+        let blue = Material {
+            color: color(0., 0., 1.),
+            ..material()
+        };
+        g.set_palette(vec![material(), red, blue]);
+        assert_eq!(g.palette().len(), 3);
+        let r = ray(point(-5., 0.5, 0.5), vector(1., 0., 0.));
+        let comps = g.local_intersect(&r)[0].prepare_computations(&r);
+        assert_eq!(comps.material.color, color(1., 0., 0.));
+        // leaving through the far side, from a voxel past the palette's end
+        let r = ray(point(5., 0.5, 0.5), vector(-1., 0., 0.));
+        let xs = g.local_intersect(&r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].prepare_computations(&r).material, g.material());
+        assert_eq!(
+            xs[1].prepare_computations(&r).material.color,
+            color(1., 0., 0.)
+        );
+        // the exit of a ray starting inside is the voxel it leaves
+        let r = ray(point(1.5, 0.5, 0.5), vector(0., 1., 0.));
+        let xs = g.local_intersect(&r);
+        assert_eq!(
+            xs[1].prepare_computations(&r).material.color,
+            color(0., 0., 1.)
+        );
+    }
+
+    #[test]
+    fn it_is_shaded_from_its_palette_in_a_world() {
+        let mut g = centre_voxel();
+        let glowing = Material {
+            color: color(0., 1., 0.),
+            ambient: 1.,
+            diffuse: 0.,
+            specular: 0.,
+            ..material()
+        };
+        let mut palette = vec![material(); 8];
+        palette[7] = glowing;
+        g.set_palette(palette);
+        let mut w = world();
+        w.objects.push(Box::new(g));
+        w.lights.push(point_light(point(1.5, 1.5, -10.), WHITE));
+        let r = ray(point(1.5, 1.5, -5.), vector(0., 0., 1.));
+        assert_eq!(w.color_at(&r), color(0., 1., 0.));
+    }
+
+    #[test]
+    fn its_normals_are_transformed_to_the_world() {
+        let mut g = centre_voxel();
+        g.set_transform(scaling(2., 2., 2.));
+        let r = ray(point(3., 3., -5.), vector(0., 0., 1.));
+        let xs = g.intersect(&r);
+        assert_abs_diff_eq!(xs[0].t, 7.);
+        let n = xs[0].object.normal_at(r.position(xs[0].t), &xs[0]);
+        assert_eq!(n, vector(0., 0., -1.));
+    }
+
+    #[test]
+    fn it_can_be_read_from_text() {
+        let text = "# a 2x1x2 grid\n2 1 2\n0 4\n# second layer\n9 0\n";
+        let g = voxel_grid_from_text(text).unwrap();
+        assert_eq!(g.size(), (2, 1, 2));
+        assert_eq!(g.get(1, 0, 0), 4);
+        assert_eq!(g.get(0, 0, 1), 9);
+        assert_eq!(g.get(0, 0, 0), 0);
+    }
+
+    #[test]
+    fn reading_text_rejects_bad_input() {
+        assert!(voxel_grid_from_text("").is_err());
+        assert!(voxel_grid_from_text("2 1 1\n1").is_err());
+        assert!(voxel_grid_from_text("1 1 1\n256").is_err());
+        assert!(voxel_grid_from_text("1 1 1\n1 2").is_err());
+        assert!(voxel_grid_from_text("0 1 1\n").is_err());
+    }
+
+    #[test]
+    fn it_can_be_read_from_raw_bytes() {
+        let g = voxel_grid_from_raw(2, 2, 1, &[0, 1, 2, 3]).unwrap();
+        assert_eq!(g.get(1, 0, 0), 1);
+        assert_eq!(g.get(0, 1, 0), 2);
+        assert!(voxel_grid_from_raw(2, 2, 1, &[0, 1, 2]).is_err());
+    }
+
+    #[test]
+    fn a_size_too_large_to_count_is_an_error_not_an_overflow() {
+        assert!(voxel_grid_from_raw(usize::MAX, 2, 1, &[]).is_err());
+        assert!(voxel_grid_from_text(&format!("{} 2 2\n1", usize::MAX)).is_err());
+    }
+}
+
+/// A block of unit cubes, like a 3D image: each voxel holds an index into
+/// the grid's palette of materials, with 0 meaning empty. Indices beyond the
+/// end of the palette are shaded with the grid's own material.
+///
+/// Voxel `(x, y, z)` fills the cube from `(x, y, z)` to `(x + 1, y + 1,
+/// z + 1)`, so scale the grid with its transform. Rays walk the grid a voxel
+/// at a time (Amanatides and Woo's DDA) and hit wherever they pass between
+/// empty and solid voxels, with the normal of the face they cross.
+#[derive(Debug, Clone)]
+pub struct VoxelGrid {
+    base: ShapeBase,
+    size: [usize; 3],
+    voxels: Vec<u8>,
+    palette: Vec<Material>,
+}
+
+impl VoxelGrid {
+    pub fn size(&self) -> (usize, usize, usize) {
+        (self.size[0], self.size[1], self.size[2])
+    }
+
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        let [nx, ny, nz] = self.size;
+        if x >= nx || y >= ny || z >= nz {
+            panic!(
+                "voxel ({}, {}, {}) is outside a {}x{}x{} grid",
+                x, y, z, nx, ny, nz
+            );
+        }
+        (z * ny + y) * nx + x
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.voxels[self.index(x, y, z)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, material: u8) {
+        let index = self.index(x, y, z);
+        self.voxels[index] = material;
+    }
+
+    pub fn palette(&self) -> &[Material] {
+        &self.palette
+    }
+
+    /// Entry `i` is the material of voxels holding `i`. Entry 0 is never
+    /// drawn, as those voxels are empty.
+    pub fn set_palette(&mut self, palette: Vec<Material>) {
+        self.palette = palette;
+    }
+
+    fn hit(&self, t: f64, normal: Vector, voxel: u8) -> Intersection<'_> {
+        Intersection {
+            material: self.palette.get(voxel as usize),
+            ..intersection_with_normal(t, self, normal)
+        }
+    }
+}
+
+impl Shape for VoxelGrid {
+    fn base(&self) -> &ShapeBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ShapeBase {
+        &mut self.base
+    }
+
+    fn local_intersect(&self, local_ray: &Ray) -> Intersections<'_> {
+        let o = local_ray.origin;
+        let d = local_ray.direction;
+        let slabs: Vec<(f64, f64)> = (0..3)
+            .map(|axis| check_slab(o[axis], d[axis], 0., self.size[axis] as f64))
+            .collect();
+        let (entry_axis, tmin) = (0..3)
+            .map(|axis| (axis, slabs[axis].0))
+            .fold((0, -f64::INFINITY), |a, b| if b.1 > a.1 { b } else { a });
+        let (exit_axis, tmax) = (0..3)
+            .map(|axis| (axis, slabs[axis].1))
+            .fold((0, f64::INFINITY), |a, b| if b.1 < a.1 { b } else { a });
+        if tmin > tmax {
+            return intersections(vec![]);
+        }
+
+        let entry = local_ray.position(tmin);
+        let mut cell = [0; 3];
+        let mut step = [0; 3];
+        let mut t_next = [f64::INFINITY; 3];
+        let mut t_delta = [f64::INFINITY; 3];
+        for axis in 0..3 {
+            cell[axis] = (entry[axis].floor().max(0.) as usize).min(self.size[axis] - 1);
+            if d[axis] > 0. {
+                step[axis] = 1;
+                t_next[axis] = ((cell[axis] + 1) as f64 - o[axis]) / d[axis];
+                t_delta[axis] = 1. / d[axis];
+            } else if d[axis] < 0. {
+                step[axis] = -1;
+                t_next[axis] = (cell[axis] as f64 - o[axis]) / d[axis];
+                t_delta[axis] = -1. / d[axis];
+            }
+        }
+
+        // crossing into a solid voxel, the face looks back at the ray;
+        // crossing out of one, it looks ahead
+        let face = |axis: usize, entering: bool| {
+            let mut n = [0.; 3];
+            n[axis] = if entering { -step[axis] } else { step[axis] } as f64;
+            vector(n[0], n[1], n[2])
+        };
+        let mut xs = vec![];
+        // the voxel the ray is inside of, or 0 when it is in empty space
+        let mut inside = 0;
+        let (mut t, mut crossed) = (tmin, entry_axis);
+        loop {
+            let voxel = self.get(cell[0], cell[1], cell[2]);
+            if voxel != 0 && inside == 0 {
+                xs.push(self.hit(t, face(crossed, true), voxel));
+            } else if voxel == 0 && inside != 0 {
+                xs.push(self.hit(t, face(crossed, false), inside));
+            }
+            inside = voxel;
+            let axis = (0..3)
+                .min_by(|&a, &b| t_next[a].total_cmp(&t_next[b]))
+                .unwrap();
+            if t_next[axis] > tmax {
+                break;
+            }
+            let next = cell[axis] as isize + step[axis];
+            if next < 0 || next >= self.size[axis] as isize {
+                break;
+            }
+            cell[axis] = next as usize;
+            t = t_next[axis];
+            t_next[axis] += t_delta[axis];
+            crossed = axis;
+        }
+        if inside != 0 {
+            xs.push(self.hit(tmax, face(exit_axis, false), inside));
+        }
+        intersections(xs)
+    }
+
+    fn local_normal_at(&self, _local_point: Point, hit: &Intersection) -> Vector {
+        match hit.normal {
+            Some(normal) => normal,
+            None => panic!("voxel grid hits carry their normal from intersection"),
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        aabb(
+            point(0., 0., 0.),
+            point(
+                self.size[0] as f64,
+                self.size[1] as f64,
+                self.size[2] as f64,
+            ),
+        )
+    }
+}
+
+/// An empty grid of `x` by `y` by `z` voxels.
+pub fn voxel_grid(x: usize, y: usize, z: usize) -> VoxelGrid {
+    let count = voxel_count(x, y, z).unwrap_or_else(|e| panic!("{}", e));
+    VoxelGrid {
+        base: ShapeBase::default(),
+        size: [x, y, z],
+        voxels: vec![0; count],
+        palette: vec![],
+    }
+}
+
+fn voxel_count(x: usize, y: usize, z: usize) -> Result<usize, String> {
+    if x == 0 || y == 0 || z == 0 {
+        return Err(format!("grid size {}x{}x{} is empty", x, y, z));
+    }
+    x.checked_mul(y)
+        .and_then(|xy| xy.checked_mul(z))
+        .ok_or_else(|| format!("grid size {}x{}x{} is too large", x, y, z))
+}
+
+/// A grid from one byte per voxel, `x` varying fastest and then `y`, as
+/// written by most simulation and volume tools.
+pub fn voxel_grid_from_raw(
+    x: usize,
+    y: usize,
+    z: usize,
+    bytes: &[u8],
+) -> Result<VoxelGrid, String> {
+    let count = voxel_count(x, y, z)?;
+    if bytes.len() != count {
+        return Err(format!(
+            "expected {} bytes for a {}x{}x{} grid, found {}",
+            count,
+            x,
+            y,
+            z,
+            bytes.len()
+        ));
+    }
+    let mut grid = voxel_grid(x, y, z);
+    grid.voxels.copy_from_slice(bytes);
+    Ok(grid)
+}
+
+/// A grid from whitespace separated numbers: the size along x, y and z,
+/// then a material index from 0 to 255 per voxel in the same order as
+/// `voxel_grid_from_raw`. Lines starting with `#` are comments.
+pub fn voxel_grid_from_text(text: &str) -> Result<VoxelGrid, String> {
+    let mut numbers = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(|line| line.split_whitespace());
+    let mut size = [0; 3];
+    for (axis, name) in ["x", "y", "z"].iter().enumerate() {
+        let number = numbers
+            .next()
+            .ok_or_else(|| format!("missing grid size along {}", name))?;
+        size[axis] = number
+            .parse::<usize>()
+            .map_err(|_| format!("invalid grid size '{}'", number))?;
+    }
+    voxel_count(size[0], size[1], size[2])?;
+    let bytes = numbers
+        .map(|number| {
+            number
+                .parse::<u8>()
+                .map_err(|_| format!("invalid material index '{}'", number))
+        })
+        .collect::<Result<Vec<u8>, String>>()?;
+    voxel_grid_from_raw(size[0], size[1], size[2], &bytes)
+}
@@ -0,0 +1,340 @@
+use crate::color::{Color, BLACK};
+use crate::intersection::{intersections, Computations, Intersection, Intersections};
+use crate::light::PointLight;
+use crate::material::lighting;
+use crate::ray::{ray_at_time, Ray};
+use crate::shape::Shape;
+use crate::tuple::Point;
+
+#[cfg(test)]
+mod tests_for_world {
+    use crate::color::{color, WHITE};
+    use crate::intersection::intersection;
+    use crate::light::point_light;
+    use crate::material::{material, Material};
+    use crate::plane::plane;
+    use crate::ray::ray;
+    use crate::shape::same_shape;
+    use crate::sphere::sphere;
+    use crate::transform::{rotation_x, translation};
+    use crate::tuple::{point, vector};
+    use crate::world::*;
+    use std::f64::consts::{FRAC_1_SQRT_2, PI, SQRT_2};
+
+    fn with_mirror_floor(w: &mut World) {
+        let mut floor = plane();
+        floor.set_material(Material {
+            reflective: 0.5,
+            ..material()
+        });
+        floor.set_transform(translation(0., -1., 0.));
+        w.objects.push(Box::new(floor));
+    }
+
+    fn ray_towards_floor() -> Ray {
+        ray(
+            point(0., 0., -3.),
+            vector(0., -FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+        )
+    }
+
+    #[test]
+    fn it_starts_empty() {
+        let w = world();
+        assert!(w.objects.is_empty());
+        assert!(w.lights.is_empty());
+        assert_eq!(w.reflection_depth, 5);
+    }
+
+    #[test]
+    fn a_ray_is_intersected_with_every_object() {
+        let w = default_world();
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let ts: Vec<f64> = w.intersect_world(&r).iter().map(|i| i.t).collect();
+        assert_eq!(ts, vec![4., 4.5, 5.5, 6.]);
+    }
+
+    #[test]
+    fn the_hit_is_the_nearest_intersection_in_front_of_the_ray() {
+        let w = default_world();
+        let r = ray(point(0., 0., 0.), vector(0., 0., 1.));
+        let hit = w.hit(&r).unwrap();
+        assert_abs_diff_eq!(hit.t, 0.5);
+        assert!(same_shape(hit.object, w.objects[1].as_ref()));
+        assert!(w
+            .hit(&ray(point(0., 0., -5.), vector(0., 1., 0.)))
+            .is_none());
+    }
+
+    #[test]
+    fn shading_a_hit() {
+        let w = default_world();
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let comps = intersection(4., w.objects[0].as_ref()).prepare_computations(&r);
+        assert_eq!(w.shade_hit(&comps, 1), color(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    fn shading_a_hit_from_the_inside() {
+        let mut w = default_world();
+        w.lights = vec![point_light(point(0., 0.25, 0.), WHITE)];
+        let r = ray(point(0., 0., 0.), vector(0., 0., 1.));
+        let comps = intersection(0.5, w.objects[1].as_ref()).prepare_computations(&r);
+        assert_eq!(w.shade_hit(&comps, 1), color(0.90498, 0.90498, 0.90498));
+    }
+
+    #[test]
+    fn a_hit_in_shadow_only_gets_ambient_light() {
+        let mut w = world();
+        w.lights.push(point_light(point(0., 0., -10.), WHITE));
+        w.objects.push(Box::new(sphere()));
+        let mut s = sphere();
+        s.set_transform(translation(0., 0., 10.));
+        w.objects.push(Box::new(s));
+        let r = ray(point(0., 0., 5.), vector(0., 0., 1.));
+        let comps = intersection(4., w.objects[1].as_ref()).prepare_computations(&r);
+        assert_eq!(w.shade_hit(&comps, 1), color(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn each_light_adds_its_own_contribution() {
+        let mut w = default_world();
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        let one = w.color_at(&r);
+        w.lights.push(w.lights[0]);
+        assert_eq!(w.color_at(&r), one * 2.);
+    }
+
+    #[test]
+    fn a_ray_that_misses_is_black() {
+        let w = default_world();
+        let r = ray(point(0., 0., -5.), vector(0., 1., 0.));
+        assert_eq!(w.color_at(&r), BLACK);
+    }
+
+    #[test]
+    fn a_ray_that_hits_is_shaded() {
+        let w = default_world();
+        let r = ray(point(0., 0., -5.), vector(0., 0., 1.));
+        assert_eq!(w.color_at(&r), color(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    fn the_nearest_hit_in_front_of_the_ray_is_used() {
+        let mut w = default_world();
+        for object in w.objects.iter_mut() {
+            let m = Material {
+                ambient: 1.,
+                ..*object.material()
+            };
+            object.set_material(m);
+        }
+        let r = ray(point(0., 0., 0.75), vector(0., 0., -1.));
+        assert_eq!(w.color_at(&r), w.objects[1].material().color);
+    }
+
+    #[test]
+    fn nothing_between_a_point_and_the_light_means_no_shadow() {
+        let w = default_world();
+        assert!(!w.is_shadowed(point(0., 10., 0.), &w.lights[0], 0.));
+    }
+
+    #[test]
+    fn an_object_between_a_point_and_the_light_casts_a_shadow() {
+        let w = default_world();
+        assert!(w.is_shadowed(point(10., -10., 10.), &w.lights[0], 0.));
+    }
+
+    #[test]
+    fn objects_behind_the_light_or_the_point_cast_no_shadow() {
+        let w = default_world();
+        assert!(!w.is_shadowed(point(-20., 20., -20.), &w.lights[0], 0.));
+        assert!(!w.is_shadowed(point(-2., 2., -2.), &w.lights[0], 0.));
+    }
+
+    #[test]
+    fn a_matt_surface_reflects_nothing() {
+        let mut w = default_world();
+        let m = Material {
+            ambient: 1.,
+            ..*w.objects[1].material()
+        };
+        w.objects[1].set_material(m);
+        let r = ray(point(0., 0., 0.), vector(0., 0., 1.));
+        let comps = intersection(1., w.objects[1].as_ref()).prepare_computations(&r);
+        assert_eq!(w.reflected_color(&comps, 1), BLACK);
+    }
+
+    #[test]
+    fn a_reflective_surface_reflects_the_scene() {
+        let mut w = default_world();
+        with_mirror_floor(&mut w);
+        let r = ray_towards_floor();
+        let comps = intersection(SQRT_2, w.objects[2].as_ref()).prepare_computations(&r);
+        assert_eq!(
+            w.reflected_color(&comps, 1),
+            color(0.19033, 0.23791, 0.14274)
+        );
+        assert_eq!(w.shade_hit(&comps, 1), color(0.87676, 0.92434, 0.82917));
+    }
+
+    #[test]
+    fn nothing_is_reflected_once_the_depth_runs_out() {
+        let mut w = default_world();
+        with_mirror_floor(&mut w);
+        let r = ray_towards_floor();
+        let comps = intersection(SQRT_2, w.objects[2].as_ref()).prepare_computations(&r);
+        assert_eq!(w.reflected_color(&comps, 0), BLACK);
+    }
+
+    #[test]
+    fn the_reflection_depth_can_be_configured() {
+        let mut w = default_world();
+        with_mirror_floor(&mut w);
+        let r = ray_towards_floor();
+        let reflecting = w.color_at(&r);
+        w.reflection_depth = 0;
+        let comps = intersection(SQRT_2, w.objects[2].as_ref()).prepare_computations(&r);
+        assert_eq!(w.color_at(&r), w.shade_hit(&comps, 0));
+        assert!(w.color_at(&r) != reflecting);
+    }
+
+    #[test]
+    fn facing_mirrors_do_not_reflect_forever() {
+        let mut w = world();
+        w.lights.push(point_light(point(0., 0., 0.), WHITE));
+        for (y, turn) in [(-1., 0.), (1., PI)].iter() {
+            let mut mirror = plane();
+            mirror.set_material(Material {
+                reflective: 1.,
+                ..material()
+            });
+            mirror.set_transform(translation(0., *y, 0.) * rotation_x(*turn));
+            w.objects.push(Box::new(mirror));
+        }
+        let r = ray(point(0., 0., 0.), vector(0., 1., 0.));
+        // each bounce adds a little ambient light from the next mirror
+        assert!(w.color_at(&r).red > 0.);
+    }
+}
+
+/// Everything in a scene: the objects to draw and the lights they are lit
+/// by.
+///
+/// Rays bounce off reflective surfaces at most `reflection_depth` times, so
+/// two facing mirrors do not reflect each other forever.
+#[derive(Debug)]
+pub struct World {
+    pub objects: Vec<Box<dyn Shape>>,
+    pub lights: Vec<PointLight>,
+    pub reflection_depth: usize,
+}
+
+impl Default for World {
+    fn default() -> World {
+        World {
+            objects: vec![],
+            lights: vec![],
+            reflection_depth: 5,
+        }
+    }
+}
+
+impl World {
+    pub fn intersect_world(&self, ray: &Ray) -> Intersections<'_> {
+        intersections(
+            self.objects
+                .iter()
+                .flat_map(|object| object.intersect(ray))
+                .collect(),
+        )
+    }
+
+    /// The color of a prepared hit, summed over every light that can see it,
+    /// plus whatever it reflects with `remaining` bounces left.
+    pub fn shade_hit(&self, comps: &Computations, remaining: usize) -> Color {
+        let surface = self.lights.iter().fold(BLACK, |total, light| {
+            total
+                + lighting(
+                    comps.material,
+                    light,
+                    comps.over_point,
+                    comps.eyev,
+                    comps.normalv,
+                    self.is_shadowed(comps.over_point, light, comps.time),
+                )
+        });
+        surface + self.reflected_color(comps, remaining)
+    }
+
+    /// The nearest hit along `ray` at or in front of its origin.
+    pub fn hit(&self, ray: &Ray) -> Option<Intersection<'_>> {
+        self.objects
+            .iter()
+            .filter_map(|object| object.hit(ray))
+            .min_by(|a, b| a.t.total_cmp(&b.t))
+    }
+
+    /// The color seen along `ray`: black if it hits nothing.
+    pub fn color_at(&self, ray: &Ray) -> Color {
+        self.color_at_depth(ray, self.reflection_depth)
+    }
+
+    pub fn color_at_depth(&self, ray: &Ray, remaining: usize) -> Color {
+        match self.hit(ray) {
+            Some(hit) => self.shade_hit(&hit.prepare_computations(ray), remaining),
+            None => BLACK,
+        }
+    }
+
+    /// The color a prepared hit picks up from the scene reflected in it.
+    pub fn reflected_color(&self, comps: &Computations, remaining: usize) -> Color {
+        let reflective = comps.material.reflective;
+        if remaining == 0 || reflective == 0. {
+            return BLACK;
+        }
+        let reflect_ray = ray_at_time(comps.over_point, comps.reflectv, comps.time);
+        self.color_at_depth(&reflect_ray, remaining - 1) * reflective
+    }
+
+    /// Whether anything lies between `point` and `light` at the given time.
+    pub fn is_shadowed(&self, point: Point, light: &PointLight, time: f64) -> bool {
+        let to_light = light.position - point;
+        let distance = to_light.magnitude();
+        let shadow_ray = ray_at_time(point, to_light.normalize(), time);
+        self.objects
+            .iter()
+            .any(|object| object.hits_before(&shadow_ray, distance))
+    }
+}
+
+pub fn world() -> World {
+    World::default()
+}
+
+/// Two spheres, one inside the other, lit from above and to the left: the
+/// scene most of the shading tests look at.
+#[cfg(test)]
+pub fn default_world() -> World {
+    use crate::color::{color, WHITE};
+    use crate::light::point_light;
+    use crate::material::{material, Material};
+    use crate::sphere::sphere;
+    use crate::transform::scaling;
+    use crate::tuple::point;
+
+    let mut s1 = sphere();
+    s1.set_material(Material {
+        color: color(0.8, 1., 0.6),
+        diffuse: 0.7,
+        specular: 0.2,
+        ..material()
+    });
+    let mut s2 = sphere();
+    s2.set_transform(scaling(0.5, 0.5, 0.5));
+    World {
+        objects: vec![Box::new(s1), Box::new(s2)],
+        lights: vec![point_light(point(-10., 10., -10.), WHITE)],
+        ..world()
+    }
+}